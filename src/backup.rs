@@ -0,0 +1,70 @@
+use crate::{error::Error, relative_path::*, sink::Sink};
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use tar::{Archive, Builder, Header};
+
+/// Location of the archive holding the files overwritten by the last apply.
+pub fn path() -> Result<PathBuf, Error> {
+    Ok(path_in(&root()?))
+}
+
+/// Location of the archive for the reconf root `root`.
+pub fn path_in(root: &Path) -> PathBuf {
+    Dir::Config.base_in(root).join("backup.tar")
+}
+
+/// Archives the targets that exist in `sink` to `archive`, returning the ones that were backed up.
+///
+/// The archive is written through `sink` as well, so staged applies never replace the backup of
+/// the real system.
+pub fn create(
+    sink: &mut dyn Sink,
+    archive: &Path,
+    targets: &[&Path],
+) -> Result<Vec<PathBuf>, Error> {
+    let mut builder = Builder::new(Vec::new());
+    let mut backed_up = Vec::new();
+
+    for &target in targets {
+        let contents = match sink.read(target)? {
+            Some(contents) => contents,
+            None => continue,
+        };
+        let mut header = Header::new_gnu();
+
+        header.set_size(contents.len() as u64);
+        header.set_mode(0o644);
+
+        // archive paths are relative, the leading `/` is added back when restoring
+        let path = target.components().skip(1).collect::<PathBuf>();
+
+        builder.append_data(&mut header, path, contents.as_slice())?;
+        backed_up.push(target.to_path_buf());
+    }
+
+    sink.write(archive, &builder.into_inner()?)?;
+
+    Ok(backed_up)
+}
+
+/// Writes every file in the backup archive back to where it was taken from.
+pub fn restore(sink: &mut dyn Sink) -> Result<Vec<PathBuf>, Error> {
+    let path = path()?;
+    let archive = sink
+        .read(&path)?
+        .ok_or_else(|| Error::NotFound("backup".to_string(), path))?;
+    let mut archive = Archive::new(archive.as_slice());
+    let mut restored = Vec::new();
+
+    for file in archive.entries()? {
+        let mut file = file?;
+        let path = PathBuf::from("/").join(file.path()?);
+        let mut contents = Vec::new();
+
+        file.read_to_end(&mut contents)?;
+        sink.write(&path, &contents)?;
+        restored.push(path);
+    }
+
+    Ok(restored)
+}
@@ -1,8 +1,20 @@
-use crate::{error::Error, relative_path::RelativePath};
-use clap::{command, Arg, Command};
-use std::{collections::HashMap, path::PathBuf};
+use crate::completions;
+use clap::{command, Arg, ArgAction, ArgMatches, Command};
+use reconf::{
+    error::Error,
+    profile,
+    relative_path::{self, Dir, RelativePath},
+};
+use std::{
+    collections::{BTreeMap, HashMap},
+    env,
+    path::PathBuf,
+};
 use toml::Value;
 
+/// Prefix of environment variables that override profile settings for a single apply.
+const SETTING_ENV_PREFIX: &str = "RECONF_SETTING_";
+
 #[derive(Debug)]
 pub enum Action {
     AddHelper {
@@ -13,19 +25,48 @@ pub enum Action {
         profile: RelativePath,
         module: RelativePath,
     },
+    AddPartial {
+        profile: RelativePath,
+        partial: RelativePath,
+    },
     AddTemplate {
         profile: RelativePath,
         template: RelativePath,
     },
     ApplyProfile {
+        profiles: Vec<RelativePath>,
+        overrides: BTreeMap<String, Value>,
+        watch: bool,
+    },
+    Check {
         profile: RelativePath,
     },
+    Completions {
+        shell: String,
+    },
     ChangeSetting {
         profile: RelativePath,
         name: String,
         value: Option<Value>,
     },
+    Init,
+    Interactive,
+    List {
+        dir: Option<Dir>,
+    },
+    New {
+        dir: Dir,
+        name: RelativePath,
+        profile: Option<RelativePath>,
+    },
     Nothing,
+    Render {
+        profile: RelativePath,
+        overrides: BTreeMap<String, Value>,
+        template: Option<String>,
+        target: Option<PathBuf>,
+        data: bool,
+    },
     Restore,
     RmHelper {
         profile: RelativePath,
@@ -35,17 +76,123 @@ pub enum Action {
         profile: RelativePath,
         module: RelativePath,
     },
+    RmPartial {
+        profile: RelativePath,
+        partial: RelativePath,
+    },
     RmTemplate {
         profile: RelativePath,
         template: RelativePath,
     },
+    SetTheme {
+        profile: RelativePath,
+        theme: RelativePath,
+    },
+    Show {
+        profile: RelativePath,
+    },
+    Test {
+        profile: RelativePath,
+        update: bool,
+    },
+}
+
+/// How results and errors are printed.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    #[default]
+    Text,
+    Json,
+}
+
+/// Arguments shared by every subcommand.
+#[derive(Debug, Default)]
+pub struct Global {
+    /// Directory that targets are written below instead of `/`.
+    pub root_prefix: Option<PathBuf>,
+    pub format: Format,
+}
+
+pub fn parse_setting_value(value: &str) -> Result<Value, Error> {
+    // FIXME: this is *really* bad, find some way to parse TOML without a table
+    let parse = |value: &str| {
+        toml::from_str::<'_, HashMap<String, Value>>(&format!("data = {}", value))
+            .map(|mut map| map.remove("data").expect("setting value is required"))
+    };
+
+    // values that aren't valid TOML on their own are treated as bare strings, as are datetimes
+    // which settings can't hold
+    Ok(match parse(value) {
+        Ok(Value::Datetime(_)) | Err(_) => parse(&format!("{:?}", value))?,
+        Ok(value) => value,
+    })
 }
 
-pub fn main() -> Result<Action, Error> {
+/// Collects setting overrides from the environment and `--set` arguments.
+fn setting_overrides(matches: &ArgMatches) -> Result<BTreeMap<String, Value>, Error> {
+    let mut overrides = BTreeMap::new();
+
+    // environment overrides are applied first so `--set` takes precedence, their names are
+    // matched to the profile's settings regardless of case
+    for (key, value) in env::vars() {
+        if let Some(name) = key.strip_prefix(SETTING_ENV_PREFIX) {
+            overrides.insert(name.to_lowercase(), parse_setting_value(&value)?);
+        }
+    }
+
+    for setting in matches.get_many::<String>("set").into_iter().flatten() {
+        let (name, value) = setting
+            .split_once('=')
+            .ok_or_else(|| Error::InvalidSettingOverride(setting.to_owned()))?;
+
+        overrides.insert(name.to_owned(), parse_setting_value(value)?);
+    }
+
+    Ok(overrides)
+}
+
+/// Parses the command line, global arguments are returned even if the action is invalid so the
+/// error can be printed in the requested format.
+pub fn main() -> (Global, Result<Action, Error>) {
+    completions::complete(cli);
+
     let matches = cli().get_matches();
 
+    if let Some(root) = matches.get_one::<String>("root") {
+        relative_path::set_root(PathBuf::from(root));
+    }
+
+    let global = Global {
+        root_prefix: matches.get_one::<String>("root-prefix").map(PathBuf::from),
+        format: match matches.get_one::<String>("format").map(String::as_str) {
+            Some("json") => Format::Json,
+            _ => Format::Text,
+        },
+    };
+
+    (global, action(&matches))
+}
+
+fn action(matches: &ArgMatches) -> Result<Action, Error> {
     Ok(match matches.subcommand() {
         Some(("apply", matches)) => {
+            let profiles = matches
+                .get_many::<String>("profile")
+                .expect("profile is required")
+                .map(|profile| PathBuf::from(profile).into())
+                .collect();
+
+            let overrides = setting_overrides(matches)?;
+            let watch = matches.get_flag("watch");
+
+            Action::ApplyProfile {
+                profiles,
+                overrides,
+                watch,
+            }
+        }
+
+        Some(("check", matches)) => {
             let profile = PathBuf::from(
                 matches
                     .get_one::<String>("profile")
@@ -53,7 +200,7 @@ pub fn main() -> Result<Action, Error> {
             )
             .into();
 
-            Action::ApplyProfile { profile }
+            Action::Check { profile }
         }
 
         Some(("helper", matches)) => match matches.subcommand() {
@@ -134,8 +281,119 @@ pub fn main() -> Result<Action, Error> {
             _ => Action::Nothing,
         },
 
+        Some(("completions", matches)) => Action::Completions {
+            shell: matches
+                .get_one::<String>("shell")
+                .expect("shell is required")
+                .to_owned(),
+        },
+
+        Some(("init", _)) => Action::Init,
+
+        Some(("list", matches)) => {
+            let dir = matches
+                .get_one::<String>("kind")
+                .map(|kind| match kind.as_str() {
+                    "profiles" => Dir::Profiles,
+                    "modules" => Dir::Modules,
+                    "helpers" => Dir::Helpers,
+                    "templates" => Dir::Templates,
+                    "partials" => Dir::Partials,
+                    "themes" => Dir::Themes,
+                    _ => unreachable!(),
+                });
+
+            Action::List { dir }
+        }
+
+        Some(("new", matches)) => {
+            let dir = match matches
+                .get_one::<String>("kind")
+                .expect("kind is required")
+                .as_str()
+            {
+                "profile" => Dir::Profiles,
+                "module" => Dir::Modules,
+                "helper" => Dir::Helpers,
+                "template" => Dir::Templates,
+                "partial" => Dir::Partials,
+                "theme" => Dir::Themes,
+                _ => unreachable!(),
+            };
+
+            let name =
+                PathBuf::from(matches.get_one::<String>("name").expect("name is required")).into();
+
+            let profile = matches
+                .get_one::<String>("profile")
+                .map(|profile| PathBuf::from(profile).into());
+
+            Action::New { dir, name, profile }
+        }
+
+        Some(("partial", matches)) => match matches.subcommand() {
+            Some(("add", matches)) => {
+                let profile = PathBuf::from(
+                    matches
+                        .get_one::<String>("profile")
+                        .expect("profile is required"),
+                )
+                .into();
+
+                let partial = PathBuf::from(
+                    matches
+                        .get_one::<String>("partial")
+                        .expect("partial is required"),
+                )
+                .into();
+
+                Action::AddPartial { profile, partial }
+            }
+
+            Some(("remove", matches)) => {
+                let profile = PathBuf::from(
+                    matches
+                        .get_one::<String>("profile")
+                        .expect("profile is required"),
+                )
+                .into();
+
+                let partial = PathBuf::from(
+                    matches
+                        .get_one::<String>("partial")
+                        .expect("partial is required"),
+                )
+                .into();
+
+                Action::RmPartial { profile, partial }
+            }
+            _ => Action::Nothing,
+        },
+
         Some(("restore", _)) => Action::Restore,
 
+        Some(("render", matches)) => {
+            let profile = PathBuf::from(
+                matches
+                    .get_one::<String>("profile")
+                    .expect("profile is required"),
+            )
+            .into();
+
+            let overrides = setting_overrides(matches)?;
+            let template = matches.get_one::<String>("template").cloned();
+            let target = matches.get_one::<String>("target").map(PathBuf::from);
+            let data = matches.get_flag("data");
+
+            Action::Render {
+                profile,
+                overrides,
+                template,
+                target,
+                data,
+            }
+        }
+
         Some(("settings", matches)) => match matches.subcommand() {
             Some(("set", matches)) => {
                 let profile = PathBuf::from(
@@ -150,19 +408,11 @@ pub fn main() -> Result<Action, Error> {
                     .expect("setting name is required")
                     .to_owned();
 
-                // FIXME: this is *really* bad, find some way to parse TOML without a table
-                let value = Some(toml::from_str::<'_, HashMap<String, Value>>(&format!(
-                    "data = {:?}",
+                let value = Some(parse_setting_value(
                     matches
                         .get_one::<String>("value")
-                        .expect("setting value is required")
-                ))?)
-                .map(|map| {
-                    map.values()
-                        .next()
-                        .expect("setting value is required")
-                        .to_owned()
-                });
+                        .expect("setting value is required"),
+                )?);
 
                 Action::ChangeSetting {
                     profile,
@@ -194,6 +444,17 @@ pub fn main() -> Result<Action, Error> {
             _ => Action::Nothing,
         },
 
+        Some(("show", matches)) => {
+            let profile = PathBuf::from(
+                matches
+                    .get_one::<String>("profile")
+                    .expect("profile is required"),
+            )
+            .into();
+
+            Action::Show { profile }
+        }
+
         Some(("template", matches)) => match matches.subcommand() {
             Some(("add", matches)) => {
                 let profile = PathBuf::from(
@@ -233,24 +494,127 @@ pub fn main() -> Result<Action, Error> {
             _ => Action::Nothing,
         },
 
+        Some(("test", matches)) => {
+            let profile = PathBuf::from(
+                matches
+                    .get_one::<String>("profile")
+                    .expect("profile is required"),
+            )
+            .into();
+
+            let update = matches.get_flag("update");
+
+            Action::Test { profile, update }
+        }
+
+        Some(("theme", matches)) => match matches.subcommand() {
+            Some(("set", matches)) => {
+                let profile = PathBuf::from(
+                    matches
+                        .get_one::<String>("profile")
+                        .expect("profile is required"),
+                )
+                .into();
+
+                let theme = PathBuf::from(
+                    matches
+                        .get_one::<String>("theme")
+                        .expect("theme is required"),
+                )
+                .into();
+
+                Action::SetTheme { profile, theme }
+            }
+            _ => Action::Nothing,
+        },
+
+        // without a terminal to pick a profile in, a bare `reconf` shows what else it can do
+        None if !profile::attended() => {
+            cli().print_help()?;
+            Action::Nothing
+        }
+        Some(("interactive", _)) | None => Action::Interactive,
+
         _ => Action::Nothing,
     })
 }
 
 fn cli() -> Command {
-    command!()
+    let cli = command!()
+        .arg(
+            Arg::new("root")
+                .help("The reconf root directory, overrides RECONF_HOME and reconf.toml discovery")
+                .long("root")
+                .value_name("DIR")
+                .global(true),
+        )
+        .arg(
+            Arg::new("format")
+                .help("How results and errors are printed")
+                .long("format")
+                .value_name("FORMAT")
+                .value_parser(["text", "json"])
+                .default_value("text")
+                .global(true),
+        )
+        .arg(
+            Arg::new("root-prefix")
+                .help("Write targets and the backup below DIR, skipping after_template")
+                .long("root-prefix")
+                .value_name("DIR")
+                .global(true),
+        )
         .subcommand(
             Command::new("apply")
-                .about("Apply a given profile")
+                .about("Apply one or more profiles, failing if two of them template the same target")
                 .alias("a")
                 .arg(
                     Arg::new("profile")
-                        .help("The profile to apply")
+                        .help("The profiles to apply, together with a single backup")
+                        .value_name("PROFILE")
+                        .index(1)
+                        .num_args(1..)
+                        .required(true),
+                )
+                .arg(
+                    Arg::new("set")
+                        .help("Override a setting for this apply only, profiles with a schema only get the settings they declare")
+                        .long("set")
+                        .value_name("NAME=VALUE")
+                        .action(ArgAction::Append),
+                )
+                .arg(
+                    Arg::new("watch")
+                        .help("Keep running and re-apply whenever a file of the profile changes")
+                        .long("watch")
+                        .short('w')
+                        .action(ArgAction::SetTrue),
+                ),
+        )
+        .subcommand(
+            Command::new("check")
+                .about("Check a given profile for problems without applying it")
+                .alias("c")
+                .arg(
+                    Arg::new("profile")
+                        .help("The profile to check")
                         .value_name("PROFILE")
                         .index(1)
                         .required(true),
                 ),
         )
+        .subcommand(
+            Command::new("completions")
+                .about("Print the completion script for a shell, e.g. `source <(reconf completions bash)`")
+                .arg(
+                    Arg::new("shell")
+                        .help("The shell to complete in")
+                        .value_name("SHELL")
+                        .value_parser(["bash", "zsh", "fish"])
+                        .index(1)
+                        .required(true),
+                ),
+        )
         .subcommand(
             Command::new("helper")
                 .about("Add or remove helpers from a given profile")
@@ -337,6 +701,143 @@ fn cli() -> Command {
                         ),
                 ),
         )
+        .subcommand(
+            Command::new("init")
+                .about("Create the reconf directory layout with an example profile"),
+        )
+        .subcommand(
+            Command::new("interactive")
+                .about("Pick a profile, edit its settings and apply it (default in a terminal)")
+                .alias("i"),
+        )
+        .subcommand(
+            Command::new("list")
+                .about("List profiles, modules, helpers, templates, partials, or themes")
+                .alias("ls")
+                .arg(
+                    Arg::new("kind")
+                        .help("The kind of component to list, lists everything if omitted")
+                        .value_name("KIND")
+                        .value_parser([
+                            "profiles",
+                            "modules",
+                            "helpers",
+                            "templates",
+                            "partials",
+                            "themes",
+                        ])
+                        .index(1),
+                ),
+        )
+        .subcommand(
+            Command::new("new")
+                .about("Create a new profile, module, helper, template, partial, or theme")
+                .alias("n")
+                .arg(
+                    Arg::new("kind")
+                        .help("The kind of file to create")
+                        .value_name("KIND")
+                        .value_parser([
+                            "profile", "module", "helper", "template", "partial", "theme",
+                        ])
+                        .index(1)
+                        .required(true),
+                )
+                .arg(
+                    Arg::new("name")
+                        .help("The name of the new file")
+                        .value_name("NAME")
+                        .index(2)
+                        .required(true),
+                )
+                .arg(
+                    Arg::new("profile")
+                        .help("The profile to add the new component to, or use the new theme in")
+                        .long("profile")
+                        .short('p')
+                        .value_name("PROFILE"),
+                ),
+        )
+        .subcommand(
+            Command::new("partial")
+                .about("Add or remove partials from a given profile")
+                .aliases(&["part", "p"])
+                .subcommand(
+                    Command::new("add")
+                        .about("Add a partial for a given profile")
+                        .alias("a")
+                        .arg(
+                            Arg::new("partial")
+                                .help("The partial to add")
+                                .value_name("PARTIAL")
+                                .index(1)
+                                .required(true),
+                        )
+                        .arg(
+                            Arg::new("profile")
+                                .help("The profile to add to")
+                                .value_name("PROFILE")
+                                .index(2)
+                                .required(true),
+                        ),
+                )
+                .subcommand(
+                    Command::new("remove")
+                        .about("Remove a partial for a given profile")
+                        .aliases(&["rm", "r"])
+                        .arg(
+                            Arg::new("partial")
+                                .help("The partial to remove")
+                                .value_name("PARTIAL")
+                                .index(1)
+                                .required(true),
+                        )
+                        .arg(
+                            Arg::new("profile")
+                                .help("The profile to remove from")
+                                .value_name("PROFILE")
+                                .index(2)
+                                .required(true),
+                        ),
+                ),
+        )
+        .subcommand(
+            Command::new("render")
+                .about("Print the rendered templates of a given profile without writing them")
+                .arg(
+                    Arg::new("profile")
+                        .help("The profile to render")
+                        .value_name("PROFILE")
+                        .index(1)
+                        .required(true),
+                )
+                .arg(
+                    Arg::new("template")
+                        .help("Only render targets using this template")
+                        .long("template")
+                        .short('t')
+                        .value_name("NAME"),
+                )
+                .arg(
+                    Arg::new("target")
+                        .help("Only render the target at this path")
+                        .long("target")
+                        .value_name("PATH"),
+                )
+                .arg(
+                    Arg::new("data")
+                        .help("Also print the data passed to each template as JSON")
+                        .long("data")
+                        .action(ArgAction::SetTrue),
+                )
+                .arg(
+                    Arg::new("set")
+                        .help("Override a setting for this render only")
+                        .long("set")
+                        .value_name("NAME=VALUE")
+                        .action(ArgAction::Append),
+                ),
+        )
         .subcommand(
             Command::new("restore")
                 .about("Restore backed up config files")
@@ -366,7 +867,7 @@ fn cli() -> Command {
                         )
                         .arg(
                             Arg::new("value")
-                                .help("The (new) value of the setting")
+                                .help("The (new) value of the setting, read as TOML (e.g. 12, true or [1, 2]) and as a string if that fails or gives a datetime")
                                 .value_name("VALUE")
                                 .index(3)
                                 .required(true),
@@ -392,6 +893,17 @@ fn cli() -> Command {
                         ),
                 ),
         )
+        .subcommand(
+            Command::new("show")
+                .about("Show the components and settings of a given profile")
+                .arg(
+                    Arg::new("profile")
+                        .help("The profile to show")
+                        .value_name("PROFILE")
+                        .index(1)
+                        .required(true),
+                ),
+        )
         .subcommand(
             Command::new("template")
                 .about("Add or remove templates from a given profile")
@@ -435,4 +947,48 @@ fn cli() -> Command {
                         ),
                 ),
         )
+        .subcommand(
+            Command::new("test")
+                .about("Compare the output of a given profile against the expected files in tests/")
+                .arg(
+                    Arg::new("profile")
+                        .help("The profile to test")
+                        .value_name("PROFILE")
+                        .index(1)
+                        .required(true),
+                )
+                .arg(
+                    Arg::new("update")
+                        .help("Overwrite the expected files with the current output")
+                        .long("update")
+                        .short('u')
+                        .action(ArgAction::SetTrue),
+                ),
+        )
+        .subcommand(
+            Command::new("theme")
+                .about("Change the theme of a given profile")
+                .subcommand(
+                    Command::new("set")
+                        .about("Set the theme of a given profile and apply it")
+                        .alias("s")
+                        .arg(
+                            Arg::new("theme")
+                                .help("The theme to use")
+                                .value_name("THEME")
+                                .index(1)
+                                .required(true),
+                        )
+                        .arg(
+                            Arg::new("profile")
+                                .help("The profile to change")
+                                .value_name("PROFILE")
+                                .index(2)
+                                .required(true),
+                        ),
+                ),
+        );
+
+    // profiles and components are looked up when completing, not when generating the script
+    completions::annotate(cli, "")
 }
@@ -0,0 +1,456 @@
+use crate::error::Error;
+use std::fmt;
+
+/// An sRGB color with alpha, every channel is stored as a float from 0 to 1.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Color {
+    pub r: f64,
+    pub g: f64,
+    pub b: f64,
+    pub a: f64,
+}
+
+/// Channel levels of the 6x6x6 color cube in the xterm 256 color palette.
+const CUBE_LEVELS: [f64; 6] = [0.0, 95.0, 135.0, 175.0, 215.0, 255.0];
+
+fn parse_hex(hex: &str) -> Option<Color> {
+    let digits = hex
+        .chars()
+        .map(|c| c.to_digit(16).map(|d| d as f64))
+        .collect::<Option<Vec<_>>>()?;
+    let channels = match digits.len() {
+        3 | 4 => digits.iter().map(|d| d * 17.0).collect::<Vec<_>>(),
+        6 | 8 => digits.chunks(2).map(|d| d[0] * 16.0 + d[1]).collect(),
+        _ => return None,
+    };
+
+    Some(Color::from_rgba8(
+        channels[0],
+        channels[1],
+        channels[2],
+        channels.get(3).map(|a| a / 255.0).unwrap_or(1.0),
+    ))
+}
+
+/// Parses the arguments of a css style function like `rgb(1, 2, 3)` or `hsl(1 2% 3%)`.
+fn parse_args<'a>(s: &'a str, name: &str) -> Option<Vec<&'a str>> {
+    let args = s
+        .strip_prefix(name)?
+        .trim()
+        .strip_prefix('(')?
+        .strip_suffix(')')?;
+
+    Some(
+        args.split(|c: char| c == ',' || c == '/' || c.is_whitespace())
+            .filter(|arg| !arg.is_empty())
+            .collect(),
+    )
+}
+
+/// Parses a number, percentages are scaled so that `100%` equals `max`.
+fn parse_number(s: &str, max: f64) -> Option<f64> {
+    match s.strip_suffix('%') {
+        Some(percent) => percent.parse::<f64>().ok().map(|p| p / 100.0 * max),
+        None => s.parse().ok(),
+    }
+}
+
+fn parse_function(s: &str) -> Option<Color> {
+    for name in ["rgba", "rgb", "hsla", "hsl"] {
+        let args = match parse_args(s, name) {
+            Some(args) => args,
+            None => continue,
+        };
+
+        if args.len() != 3 && args.len() != 4 {
+            return None;
+        }
+
+        let alpha = match args.get(3) {
+            Some(alpha) => parse_number(alpha, 1.0)?,
+            None => 1.0,
+        };
+
+        return match name {
+            "rgba" | "rgb" => Some(Color::from_rgba8(
+                parse_number(args[0], 255.0)?,
+                parse_number(args[1], 255.0)?,
+                parse_number(args[2], 255.0)?,
+                alpha,
+            )),
+            _ => Some(Color::from_hsla(
+                args[0].trim_end_matches("deg").parse().ok()?,
+                parse_number(args[1], 1.0)?,
+                parse_number(args[2], 1.0)?,
+                alpha,
+            )),
+        };
+    }
+
+    None
+}
+
+fn hue_to_channel(p: f64, q: f64, t: f64) -> f64 {
+    let t = t.rem_euclid(1.0);
+
+    match t {
+        t if t < 1.0 / 6.0 => p + (q - p) * 6.0 * t,
+        t if t < 1.0 / 2.0 => q,
+        t if t < 2.0 / 3.0 => p + (q - p) * (2.0 / 3.0 - t) * 6.0,
+        _ => p,
+    }
+}
+
+fn luminance_channel(c: f64) -> f64 {
+    if c <= 0.03928 {
+        c / 12.92
+    } else {
+        ((c + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+impl Color {
+    pub const BLACK: Color = Color::new(0.0, 0.0, 0.0, 1.0);
+    pub const WHITE: Color = Color::new(1.0, 1.0, 1.0, 1.0);
+
+    pub const fn new(r: f64, g: f64, b: f64, a: f64) -> Self {
+        Self { r, g, b, a }
+    }
+
+    /// Creates a color from 0-255 channels and a 0-1 alpha, clamping out of range values.
+    pub fn from_rgba8(r: f64, g: f64, b: f64, a: f64) -> Self {
+        Self::new(
+            (r / 255.0).clamp(0.0, 1.0),
+            (g / 255.0).clamp(0.0, 1.0),
+            (b / 255.0).clamp(0.0, 1.0),
+            a.clamp(0.0, 1.0),
+        )
+    }
+
+    /// Creates a color from a hue in degrees and 0-1 saturation, lightness and alpha.
+    pub fn from_hsla(h: f64, s: f64, l: f64, a: f64) -> Self {
+        let (h, s, l) = (
+            h.rem_euclid(360.0) / 360.0,
+            s.clamp(0.0, 1.0),
+            l.clamp(0.0, 1.0),
+        );
+
+        if s == 0.0 {
+            return Self::new(l, l, l, a.clamp(0.0, 1.0));
+        }
+
+        let q = if l < 0.5 {
+            l * (1.0 + s)
+        } else {
+            l + s - l * s
+        };
+        let p = 2.0 * l - q;
+
+        Self::new(
+            hue_to_channel(p, q, h + 1.0 / 3.0),
+            hue_to_channel(p, q, h),
+            hue_to_channel(p, q, h - 1.0 / 3.0),
+            a.clamp(0.0, 1.0),
+        )
+    }
+
+    /// Parses `#rgb`, `#rgba`, `#rrggbb`, `#rrggbbaa`, `rgb()`, `rgba()`, `hsl()` or `hsla()`.
+    pub fn parse(s: &str) -> Result<Self, Error> {
+        let trimmed = s.trim().to_lowercase();
+
+        match trimmed.strip_prefix('#') {
+            Some(hex) => parse_hex(hex),
+            None => parse_function(&trimmed).or_else(|| parse_hex(&trimmed)),
+        }
+        .ok_or_else(|| Error::InvalidColor(s.to_string()))
+    }
+
+    /// Returns the 0-255 red, green and blue channels.
+    pub fn rgb8(&self) -> [u8; 3] {
+        [self.r, self.g, self.b].map(|c| (c * 255.0).round() as u8)
+    }
+
+    /// Returns the hue in degrees and the 0-1 saturation and lightness.
+    pub fn hsl(&self) -> (f64, f64, f64) {
+        let max = self.r.max(self.g).max(self.b);
+        let min = self.r.min(self.g).min(self.b);
+        let l = (max + min) / 2.0;
+        let d = max - min;
+
+        if d == 0.0 {
+            return (0.0, 0.0, l);
+        }
+
+        let s = if l > 0.5 {
+            d / (2.0 - max - min)
+        } else {
+            d / (max + min)
+        };
+        let h = if max == self.r {
+            (self.g - self.b) / d + if self.g < self.b { 6.0 } else { 0.0 }
+        } else if max == self.g {
+            (self.b - self.r) / d + 2.0
+        } else {
+            (self.r - self.g) / d + 4.0
+        };
+
+        (h * 60.0, s, l)
+    }
+
+    /// Formats as `#rrggbb`, or `#rrggbbaa` when the color is not opaque.
+    pub fn to_hex(self) -> String {
+        let [r, g, b] = self.rgb8();
+
+        match self.a < 1.0 {
+            true => format!(
+                "#{:02x}{:02x}{:02x}{:02x}",
+                r,
+                g,
+                b,
+                (self.a * 255.0).round() as u8
+            ),
+            false => format!("#{:02x}{:02x}{:02x}", r, g, b),
+        }
+    }
+
+    pub fn to_rgb(self) -> String {
+        let [r, g, b] = self.rgb8();
+
+        format!("rgb({}, {}, {})", r, g, b)
+    }
+
+    pub fn to_rgba(self) -> String {
+        let [r, g, b] = self.rgb8();
+
+        format!("rgba({}, {}, {}, {})", r, g, b, round(self.a, 3))
+    }
+
+    pub fn to_hsl(self) -> String {
+        let (h, s, l) = self.hsl();
+
+        format!(
+            "hsl({}, {}%, {}%)",
+            round(h, 1),
+            round(s * 100.0, 1),
+            round(l * 100.0, 1)
+        )
+    }
+
+    /// Returns the red, green, blue and alpha channels as 0-1 floats.
+    pub fn to_floats(self) -> [f64; 4] {
+        [self.r, self.g, self.b, self.a].map(|c| round(c, 4))
+    }
+
+    /// Returns the closest color in the xterm 256 color palette, ignoring the 16 system colors.
+    pub fn to_ansi256(self) -> u8 {
+        let rgb = [self.r, self.g, self.b].map(|c| c * 255.0);
+        let distance =
+            |other: [f64; 3]| -> f64 { rgb.iter().zip(other).map(|(a, b)| (a - b).powi(2)).sum() };
+
+        let cube_idx = rgb.map(|c| {
+            CUBE_LEVELS
+                .iter()
+                .enumerate()
+                .min_by(|(_, a), (_, b)| (c - *a).abs().total_cmp(&(c - *b).abs()))
+                .map(|(idx, _)| idx)
+                .unwrap_or_default()
+        });
+        let cube = cube_idx.map(|idx| CUBE_LEVELS[idx]);
+
+        let gray_idx = (((rgb.iter().sum::<f64>() / 3.0 - 8.0) / 10.0).round()).clamp(0.0, 23.0);
+        let gray = 8.0 + gray_idx * 10.0;
+
+        if distance([gray; 3]) < distance(cube) {
+            232 + gray_idx as u8
+        } else {
+            16 + (36 * cube_idx[0] + 6 * cube_idx[1] + cube_idx[2]) as u8
+        }
+    }
+
+    /// Increases the HSL lightness by `amount` (0-1).
+    pub fn lighten(&self, amount: f64) -> Self {
+        let (h, s, l) = self.hsl();
+
+        Self::from_hsla(h, s, l + amount, self.a)
+    }
+
+    /// Decreases the HSL lightness by `amount` (0-1).
+    pub fn darken(&self, amount: f64) -> Self {
+        self.lighten(-amount)
+    }
+
+    /// Mixes in `other`, a `weight` of 0 returns this color and 1 returns `other`.
+    pub fn mix(&self, other: &Color, weight: f64) -> Self {
+        let weight = weight.clamp(0.0, 1.0);
+        let lerp = |a: f64, b: f64| a + (b - a) * weight;
+
+        Self::new(
+            lerp(self.r, other.r),
+            lerp(self.g, other.g),
+            lerp(self.b, other.b),
+            lerp(self.a, other.a),
+        )
+    }
+
+    pub fn with_alpha(&self, alpha: f64) -> Self {
+        Self::new(self.r, self.g, self.b, alpha.clamp(0.0, 1.0))
+    }
+
+    /// Relative luminance as defined by WCAG 2.
+    pub fn luminance(&self) -> f64 {
+        0.2126 * luminance_channel(self.r)
+            + 0.7152 * luminance_channel(self.g)
+            + 0.0722 * luminance_channel(self.b)
+    }
+
+    /// WCAG 2 contrast ratio, from 1 to 21.
+    pub fn contrast(&self, other: &Color) -> f64 {
+        let (a, b) = (self.luminance(), other.luminance());
+
+        (a.max(b) + 0.05) / (a.min(b) + 0.05)
+    }
+
+    /// Picks the candidate with the highest contrast against this color, used as a background.
+    /// Chooses between black and white when there are no candidates.
+    pub fn readable(&self, candidates: &[Color]) -> Color {
+        let default = [Color::BLACK, Color::WHITE];
+        let candidates = match candidates.is_empty() {
+            true => &default,
+            false => candidates,
+        };
+
+        candidates
+            .iter()
+            .copied()
+            .max_by(|a, b| self.contrast(a).total_cmp(&self.contrast(b)))
+            .unwrap_or(Color::BLACK)
+    }
+}
+
+fn round(value: f64, digits: i32) -> f64 {
+    let factor = 10f64.powi(digits);
+
+    (value * factor).round() / factor
+}
+
+impl fmt::Display for Color {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.to_hex())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn hex(s: &str) -> String {
+        Color::parse(s).unwrap().to_hex()
+    }
+
+    #[test]
+    fn parses_hex() {
+        assert_eq!(hex("#fff"), "#ffffff");
+        assert_eq!(hex("#f008"), "#ff000088");
+        assert_eq!(hex("#1A2b3C"), "#1a2b3c");
+        assert_eq!(hex("1a2b3c80"), "#1a2b3c80");
+        assert_eq!(hex("  #abc  "), "#aabbcc");
+    }
+
+    #[test]
+    fn parses_functions() {
+        assert_eq!(hex("rgb(255, 0, 0)"), "#ff0000");
+        assert_eq!(hex("rgb(100% 50% 0%)"), "#ff8000");
+        assert_eq!(hex("rgba(0, 0, 255, 0.5)"), "#0000ff80");
+        assert_eq!(hex("rgb(0 0 255 / 50%)"), "#0000ff80");
+        assert_eq!(hex("hsl(120, 100%, 50%)"), "#00ff00");
+        assert_eq!(hex("hsla(240deg, 100%, 50%, 0.5)"), "#0000ff80");
+    }
+
+    #[test]
+    fn clamps_out_of_range_channels() {
+        assert_eq!(hex("rgb(300, -5, 0)"), "#ff0000");
+        assert_eq!(hex("hsl(-120, 150%, 50%)"), "#0000ff");
+    }
+
+    #[test]
+    fn rejects_invalid_colors() {
+        for invalid in [
+            "",
+            "#",
+            "#ff",
+            "#fffff",
+            "#ggg",
+            "rgb(1, 2)",
+            "rgb(1, 2, x)",
+            "red",
+        ] {
+            assert!(
+                matches!(Color::parse(invalid), Err(Error::InvalidColor(s)) if s == invalid),
+                "{:?} should be invalid",
+                invalid
+            );
+        }
+    }
+
+    #[test]
+    fn converts_to_hsl_and_back() {
+        for color in [
+            "#ff0000", "#00ff00", "#0000ff", "#1a2b3c", "#808080", "#fedcba",
+        ] {
+            let (h, s, l) = Color::parse(color).unwrap().hsl();
+
+            assert_eq!(Color::from_hsla(h, s, l, 1.0).to_hex(), color);
+        }
+
+        assert_eq!(Color::parse("#ff0000").unwrap().hsl(), (0.0, 1.0, 0.5));
+        assert_eq!(
+            Color::parse("#808080").unwrap().to_hsl(),
+            "hsl(0, 0%, 50.2%)"
+        );
+        assert_eq!(
+            Color::parse("#ff00ff").unwrap().to_hsl(),
+            "hsl(300, 100%, 50%)"
+        );
+    }
+
+    #[test]
+    fn formats() {
+        let color = Color::parse("rgba(255, 128, 0, 0.25)").unwrap();
+
+        assert_eq!(color.to_rgb(), "rgb(255, 128, 0)");
+        assert_eq!(color.to_rgba(), "rgba(255, 128, 0, 0.25)");
+        assert_eq!(color.to_string(), "#ff800040");
+        assert_eq!(Color::WHITE.to_ansi256(), 231);
+        assert_eq!(Color::parse("#808080").unwrap().to_ansi256(), 244);
+    }
+
+    #[test]
+    fn mixes() {
+        let (black, white) = (Color::BLACK, Color::WHITE);
+
+        assert_eq!(black.mix(&white, 0.0), black);
+        assert_eq!(black.mix(&white, 1.0), white);
+        assert_eq!(black.mix(&white, 0.5).to_hex(), "#808080");
+        assert_eq!(black.mix(&white, 2.0), white);
+        assert_eq!(black.mix(&white.with_alpha(0.0), 0.5).to_hex(), "#80808080");
+    }
+
+    #[test]
+    fn lightens_and_darkens() {
+        let red = Color::parse("#ff0000").unwrap();
+
+        assert_eq!(red.lighten(0.25).to_hex(), "#ff8080");
+        assert_eq!(red.darken(0.25).to_hex(), "#800000");
+        assert_eq!(red.lighten(1.0), Color::WHITE);
+        assert_eq!(red.darken(1.0), Color::BLACK);
+        assert_eq!(red.with_alpha(0.5).lighten(0.1).a, 0.5);
+    }
+
+    #[test]
+    fn measures_contrast() {
+        assert!((Color::BLACK.contrast(&Color::WHITE) - 21.0).abs() < 1e-9);
+        assert_eq!(Color::WHITE.contrast(&Color::WHITE), 1.0);
+        assert_eq!(Color::parse("#222").unwrap().readable(&[]), Color::WHITE);
+        assert_eq!(Color::parse("#eee").unwrap().readable(&[]), Color::BLACK);
+    }
+}
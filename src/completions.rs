@@ -0,0 +1,125 @@
+use clap::Command;
+use clap_complete::engine::{ArgValueCandidates, CompletionCandidate};
+use clap_complete::env::{CompleteEnv, Shells};
+use reconf::{error::Error, relative_path::*, ProfileData};
+use std::collections::BTreeSet;
+use std::{env, fs, io, path::PathBuf};
+
+/// Environment variable the shell sets when asking reconf for completions.
+const COMPLETE_ENV: &str = "COMPLETE";
+
+/// Names of the components in a directory, without extensions as every argument accepts them.
+fn components(dir: Dir) -> Vec<CompletionCandidate> {
+    dir.list()
+        .unwrap_or_default()
+        .into_iter()
+        .map(|component| CompletionCandidate::new(component.with_extension("")))
+        .collect()
+}
+
+/// Names of the settings and schema entries of every profile, the profile isn't known yet when
+/// completing the setting name.
+fn settings() -> Vec<CompletionCandidate> {
+    let mut names = BTreeSet::new();
+
+    for profile in Dir::Profiles.list().unwrap_or_default() {
+        let data = RelativePath::from(profile)
+            .resolve(Dir::Profiles, "toml")
+            .ok()
+            .and_then(|path| fs::read_to_string(path).ok())
+            .and_then(|buf| toml::from_str::<ProfileData>(&buf).ok());
+
+        if let Some(data) = data {
+            names.extend(data.settings.into_keys());
+            names.extend(data.schema.into_keys());
+        }
+    }
+
+    names.into_iter().map(CompletionCandidate::new).collect()
+}
+
+/// Adds candidates to the component and setting arguments of `cmd` and its subcommands.
+pub fn annotate(cmd: Command, parent: &str) -> Command {
+    let name = cmd.get_name().to_owned();
+    let args = cmd
+        .get_arguments()
+        .map(|arg| arg.get_id().to_string())
+        .collect::<Vec<_>>();
+    let subcommands = cmd
+        .get_subcommands()
+        .map(|subcommand| subcommand.get_name().to_owned())
+        .collect::<Vec<_>>();
+    let mut cmd = cmd;
+
+    for id in args {
+        let dir = match id.as_str() {
+            "profile" => Dir::Profiles,
+            "module" => Dir::Modules,
+            "helper" => Dir::Helpers,
+            "template" => Dir::Templates,
+            "partial" => Dir::Partials,
+            "theme" => Dir::Themes,
+            "name" if parent == "settings" => {
+                cmd = cmd.mut_arg(id, |arg| arg.add(ArgValueCandidates::new(settings)));
+                continue;
+            }
+            _ => continue,
+        };
+
+        cmd = cmd.mut_arg(id, |arg| {
+            arg.add(ArgValueCandidates::new(move || components(dir)))
+        });
+    }
+
+    for subcommand in subcommands {
+        cmd = cmd.mut_subcommand(subcommand, |subcommand| annotate(subcommand, &name));
+    }
+
+    cmd
+}
+
+/// Answers the shell when it asks for completions, exiting afterwards.
+///
+/// The command line being completed comes after `--`, its `--root` is used to find components.
+pub fn complete(cli: fn() -> Command) {
+    if env::var_os(COMPLETE_ENV).is_none() {
+        return;
+    }
+
+    let args = env::args()
+        .skip_while(|arg| arg != "--")
+        .collect::<Vec<_>>();
+
+    for (idx, arg) in args.iter().enumerate() {
+        match arg.strip_prefix("--root=") {
+            Some(root) => set_root(PathBuf::from(root)),
+            None if arg == "--root" => {
+                if let Some(root) = args.get(idx + 1) {
+                    set_root(PathBuf::from(root));
+                }
+            }
+            None => {}
+        }
+    }
+
+    CompleteEnv::with_factory(cli).var(COMPLETE_ENV).complete();
+}
+
+/// Prints the script registering reconf's completions with `shell`.
+pub fn print(shell: &str) -> Result<(), Error> {
+    let shells = Shells::builtins();
+    let completer = shells
+        .completer(shell)
+        .expect("shell is one of the possible values");
+    let bin = env::current_exe()?;
+
+    completer.write_registration(
+        COMPLETE_ENV,
+        "reconf",
+        "reconf",
+        &bin.to_string_lossy(),
+        &mut io::stdout(),
+    )?;
+
+    Ok(())
+}
@@ -1,24 +1,26 @@
-use crate::{error::Error, profile::ProfileData, relative_path::*};
-use std::fs::{write, File};
-use std::{collections::HashSet, io::Read};
+use crate::{error::Error, profile::ProfileDocument, relative_path::*};
+use std::collections::HashSet;
+use std::path::Path;
 
 pub fn add(profile: RelativePath, dir: Dir, component: RelativePath) -> Result<(), Error> {
-    let mut buf = String::new();
-    let path = profile.resolve(Dir::Profiles, "toml")?;
-    let mut used = HashSet::new();
-
-    File::open(&path)?.read_to_string(&mut buf)?;
+    add_in(&root()?, profile, dir, component)
+}
 
-    let mut data: ProfileData = toml::from_str(&buf)?;
-    let components = match dir {
-        Dir::Helpers => &mut data.helpers,
-        Dir::Modules => &mut data.modules,
-        Dir::Templates => &mut data.templates,
-        _ => unreachable!(),
-    };
+/// Like `add` with the profiles and components of `root` rather than the global root.
+pub fn add_in(
+    root: &Path,
+    profile: RelativePath,
+    dir: Dir,
+    component: RelativePath,
+) -> Result<(), Error> {
+    let mut doc = ProfileDocument::open_in(root, &profile)?;
+    let path = doc.path.clone();
+    let mut used = HashSet::new();
+    let components = doc.data.components(dir);
 
     for component in components.iter() {
-        let component = RelativePath::from(component.to_path_buf()).resolve(dir, dir.ext_str())?;
+        let component =
+            RelativePath::from(component.to_path_buf()).resolve_in(root, dir, dir.ext_str())?;
 
         if used.contains(&component) {
             return Err(
@@ -29,7 +31,7 @@ pub fn add(profile: RelativePath, dir: Dir, component: RelativePath) -> Result<(
         used.insert(component);
     }
 
-    let component_path = component.resolve(dir, dir.ext_str())?;
+    let component_path = component.resolve_in(root, dir, dir.ext_str())?;
 
     if used.contains(&component_path) {
         return Err(
@@ -37,34 +39,32 @@ pub fn add(profile: RelativePath, dir: Dir, component: RelativePath) -> Result<(
         );
     }
 
-    components.push(component.path().to_path_buf());
-
-    write(path, toml::to_string(&data)?)?;
-
-    Ok(())
+    doc.push_component(dir, &component)?;
+    doc.save()
 }
 
 pub fn rm(profile: RelativePath, dir: Dir, component: RelativePath) -> Result<(), Error> {
-    let mut buf = String::new();
-    let path = profile.resolve(Dir::Profiles, "toml")?;
-    let mut used = HashSet::new();
-
-    File::open(&path)?.read_to_string(&mut buf)?;
+    rm_in(&root()?, profile, dir, component)
+}
 
-    let mut data: ProfileData = toml::from_str(&buf)?;
-    let components = match dir {
-        Dir::Helpers => &mut data.helpers,
-        Dir::Modules => &mut data.modules,
-        Dir::Templates => &mut data.templates,
-        _ => unreachable!(),
-    };
+/// Like `rm` with the profiles and components of `root` rather than the global root.
+pub fn rm_in(
+    root: &Path,
+    profile: RelativePath,
+    dir: Dir,
+    component: RelativePath,
+) -> Result<(), Error> {
+    let mut doc = ProfileDocument::open_in(root, &profile)?;
+    let path = doc.path.clone();
+    let mut used = HashSet::new();
+    let components = doc.data.components(dir);
 
-    let component_path = component.resolve_unchecked(dir, dir.ext_str())?;
+    let component_path = component.resolve_unchecked_in(root, dir, dir.ext_str());
     let mut target_component = None;
 
     for (idx, prof_component) in components.iter().enumerate() {
         let prof_component_path = RelativePath::from(prof_component.to_path_buf())
-            .resolve_unchecked(dir, dir.ext_str())?;
+            .resolve_unchecked_in(root, dir, dir.ext_str());
 
         if prof_component_path == component_path {
             target_component = Some(idx);
@@ -82,13 +82,12 @@ pub fn rm(profile: RelativePath, dir: Dir, component: RelativePath) -> Result<()
         used.insert(prof_component_path);
     }
 
-    components.remove(target_component.ok_or(Error::NotInProfile(
+    let target_component = target_component.ok_or(Error::NotInProfile(
         dir.component_str().to_string(),
         component_path,
-        path.clone(),
-    ))?);
-
-    write(path, toml::to_string(&data)?)?;
+        path,
+    ))?;
 
-    Ok(())
+    doc.remove_component(dir, target_component)?;
+    doc.save()
 }
@@ -0,0 +1,176 @@
+use handlebars::{RenderError, TemplateError};
+use rhai::{EvalAltResult, Position};
+use std::error::Error as StdError;
+use std::path::{Path, PathBuf};
+use std::{fmt, fs};
+
+/// An error at a location in a source file, displayed with an excerpt of the offending line.
+#[derive(Debug)]
+pub struct Diagnostic {
+    pub path: PathBuf,
+    pub line: Option<usize>,
+    pub column: Option<usize>,
+    pub message: String,
+    /// Extra context like the call stack, closest frame first.
+    pub notes: Vec<String>,
+    excerpt: Option<String>,
+    source: Option<Box<dyn StdError + Send + Sync>>,
+}
+
+fn location(path: &Path, pos: Position) -> String {
+    match (pos.line(), pos.position()) {
+        (Some(line), Some(column)) => format!("{}:{}:{}", path.display(), line, column),
+        (Some(line), None) => format!("{}:{}", path.display(), line),
+        _ => path.display().to_string(),
+    }
+}
+
+impl Diagnostic {
+    /// Creates a diagnostic, reading the excerpt from `path` right away so the message still
+    /// matches what was run if the file is edited afterwards.
+    pub fn new(
+        path: &Path,
+        line: Option<usize>,
+        column: Option<usize>,
+        message: impl Into<String>,
+    ) -> Self {
+        let excerpt = line.filter(|&line| line > 0).and_then(|line| {
+            fs::read_to_string(path)
+                .ok()?
+                .lines()
+                .nth(line - 1)
+                .map(str::to_owned)
+        });
+
+        Self {
+            path: path.to_path_buf(),
+            line,
+            column,
+            message: message.into(),
+            notes: Vec::new(),
+            excerpt,
+            source: None,
+        }
+    }
+
+    pub fn with_source(mut self, source: impl Into<Box<dyn StdError + Send + Sync>>) -> Self {
+        self.source = Some(source.into());
+        self
+    }
+
+    /// Converts a template syntax error in the template or partial at `path`.
+    pub fn template(path: &Path, err: &TemplateError) -> Self {
+        Self::new(path, err.line_no, err.column_no, err.reason().to_string())
+    }
+
+    /// Converts an error raised while rendering the template or partial at `path`.
+    pub fn render(path: &Path, err: RenderError) -> Self {
+        Self::new(path, err.line_no, err.column_no, err.desc.to_owned()).with_source(err)
+    }
+
+    /// Converts a Rhai error raised while running `path`, following errors in function calls and
+    /// imported modules to the file they happened in.
+    ///
+    /// Modules are found in `modules` by file stem, the name used by `import` and set as the
+    /// source of each module's AST.
+    pub fn rhai(path: &Path, mut err: EvalAltResult, modules: &[PathBuf]) -> Self {
+        let find = |name: &str| {
+            modules
+                .iter()
+                .find(|module| module.file_stem().is_some_and(|stem| stem == name))
+                .cloned()
+        };
+        let mut path = path.to_path_buf();
+        let mut notes = Vec::new();
+
+        let mut err = loop {
+            err = match err {
+                EvalAltResult::ErrorInFunctionCall(name, source, inner, pos) => {
+                    notes.push(format!(
+                        "in call to function `{}` at {}",
+                        name,
+                        location(&path, pos)
+                    ));
+
+                    if let Some(module) = find(&source) {
+                        path = module;
+                    }
+
+                    *inner
+                }
+                EvalAltResult::ErrorInModule(name, inner, pos) => {
+                    notes.push(format!(
+                        "in module `{}` imported at {}",
+                        name,
+                        location(&path, pos)
+                    ));
+
+                    if let Some(module) = find(&name) {
+                        path = module;
+                    }
+
+                    *inner
+                }
+                err => break err,
+            };
+        };
+
+        let pos = err.take_position();
+
+        notes.reverse();
+
+        Self {
+            notes,
+            ..Self::new(&path, pos.line(), pos.position(), err.to_string())
+        }
+    }
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}\n  --> {}", self.message, self.path.display())?;
+
+        if let Some(line) = self.line {
+            write!(f, ":{}", line)?;
+
+            if let Some(column) = self.column {
+                write!(f, ":{}", column)?;
+            }
+        }
+
+        if let (Some(line), Some(excerpt)) = (self.line, &self.excerpt) {
+            let gutter = " ".repeat(line.to_string().len());
+
+            write!(f, "\n{} |\n{} | {}", gutter, line, excerpt)?;
+
+            // point at the column, keeping tabs so the caret lines up with the excerpt
+            if let Some(column) = self.column.filter(|&column| column > 0) {
+                let padding = excerpt
+                    .chars()
+                    .take(column - 1)
+                    .map(|c| if c == '\t' { '\t' } else { ' ' })
+                    .collect::<String>();
+
+                write!(f, "\n{} | {}^", gutter, padding)?;
+            }
+        }
+
+        for note in &self.notes {
+            write!(f, "\n  = note: {}", note)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl StdError for Diagnostic {
+    fn source(&self) -> Option<&(dyn StdError + 'static)> {
+        let source = self.source.as_deref()?;
+
+        // render errors only repeat the message and location shown above, skip to their cause
+        match source.downcast_ref::<RenderError>() {
+            Some(err) => err.source(),
+            None => Some(source),
+        }
+    }
+}
@@ -31,20 +31,21 @@ impl ModuleResolver for DynamicModuleResolver {
         path: &str,
         pos: Position,
     ) -> Result<Shared<Module>, Box<EvalAltResult>> {
-        let ast = engine.compile_file(
-            self.0
-                .get(path)
-                .ok_or(Box::new(EvalAltResult::ErrorModuleNotFound(
-                    path.to_string(),
-                    pos,
-                )))?
-                .to_path_buf(),
-        )?;
+        let file = self
+            .0
+            .get(path)
+            .ok_or(Box::new(EvalAltResult::ErrorModuleNotFound(
+                path.to_string(),
+                pos,
+            )))?;
+        let in_module = |err| Box::new(EvalAltResult::ErrorInModule(path.to_string(), err, pos));
+        let mut ast = engine.compile_file(file.to_path_buf()).map_err(in_module)?;
 
-        Ok(Arc::new(Module::eval_ast_as_new(
-            Scope::new(),
-            &ast,
-            &engine,
-        )?))
+        // named like the import so errors can be traced back to the file
+        ast.set_source(path);
+
+        Ok(Arc::new(
+            Module::eval_ast_as_new(Scope::new(), &ast, &engine).map_err(in_module)?,
+        ))
     }
 }
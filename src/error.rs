@@ -1,8 +1,34 @@
-use std::path::PathBuf;
+use crate::diagnostic::Diagnostic;
+use serde::{ser::SerializeMap, Serialize, Serializer};
+use std::path::{Path, PathBuf};
 
 use thiserror::Error;
 
+/// Prints an error to stderr followed by every error that caused it.
+///
+/// Collected errors are printed one after another before the summary.
+pub fn print_chain(err: &(dyn std::error::Error + 'static)) {
+    if let Some(Error::ProfileErrors(errors, _) | Error::ProfilesFailed(errors)) =
+        err.downcast_ref::<Error>()
+    {
+        for err in errors {
+            print_chain(err);
+            eprintln!();
+        }
+    }
+
+    eprintln!("{}", err);
+
+    let mut source = err.source();
+
+    while let Some(err) = source {
+        eprintln!("caused by: {}", err);
+        source = err.source();
+    }
+}
+
 #[derive(Debug, Error)]
+#[non_exhaustive]
 pub enum Error {
     #[error("could not find config directory with XDG lookup")]
     NoConfigDir,
@@ -13,6 +39,9 @@ pub enum Error {
     #[error("could not find {0} {1:?}")]
     NotFound(String, PathBuf),
 
+    #[error("{0} {1:?} already exists")]
+    AlreadyExists(String, PathBuf),
+
     #[error("{0} {1:?} is not in profile {2:?}")]
     NotInProfile(String, PathBuf, PathBuf),
 
@@ -22,30 +51,227 @@ pub enum Error {
     #[error("{0} {1:?} is used twice in profile {2:?}")]
     DupeProfVal(String, PathBuf, PathBuf),
 
+    #[error("could not edit {0:?} in profile {1:?}, it is neither a table nor an array")]
+    UneditableProfile(String, PathBuf),
+
     #[error("attempted to template {0:?} after already templating")]
     TemplateTwice(PathBuf),
 
+    #[error("template targets must be absolute paths, got {0:?}")]
+    RelativeTarget(PathBuf),
+
+    #[error("{0:?} is templated by both profile {1:?} and profile {2:?}")]
+    TemplateTwiceAcrossProfiles(PathBuf, PathBuf, PathBuf),
+
+    #[error("invalid color {0:?}, expected hex, rgb(), rgba(), hsl() or hsla()")]
+    InvalidColor(String),
+
+    #[error("color {0:?} in theme {1:?} is invalid\ncaused by: {2}")]
+    InvalidThemeColor(String, PathBuf, Box<Error>),
+
+    #[error("no target matches the given template or target path")]
+    NoRenderTarget,
+
+    #[error("interactive mode needs a terminal, see `reconf --help` for other commands")]
+    NotATerminal,
+
     #[error("toml deserialize error")]
     TomlDeError(#[from] toml::de::Error),
 
     #[error("toml serialize error")]
     TomlSeError(#[from] toml::ser::Error),
 
+    #[error("toml edit error")]
+    TomlEditError(#[from] toml_edit::TomlError),
+
     #[error("io error")]
     IoError(#[from] std::io::Error),
 
-    #[error("handlebars template error")]
+    #[error("json serialize error")]
+    JsonError(#[from] serde_json::Error),
+
+    #[error("file watcher error")]
+    WatchError(#[from] notify_debouncer_mini::notify::Error),
+
+    #[error(transparent)]
     TemplateError(#[from] handlebars::TemplateError),
 
-    #[error("handlebars render error")]
+    #[error("could not parse template {0:?}")]
+    TemplateSyntaxError(PathBuf, #[source] Box<Diagnostic>),
+
+    #[error("handlebars render error: {}", .0.desc)]
     RenderError(#[from] handlebars::RenderError),
 
-    #[error("handlebars script helper error\ncaused by: {0}")]
-    ScriptHelperError(Box<dyn std::error::Error>),
+    #[error("could not render template {0:?}")]
+    TemplateRenderError(PathBuf, #[source] Box<Diagnostic>),
+
+    #[error("error in script helper {0:?}")]
+    ScriptHelperError(PathBuf, #[source] Box<Diagnostic>),
+
+    #[error("error in rhai module {0:?}")]
+    RhaiModuleError(PathBuf, #[source] Box<Diagnostic>),
+
+    #[error("setting override {0:?} must be in the form NAME=VALUE")]
+    InvalidSettingOverride(String),
+
+    #[error("setting {0:?} in profile {3:?} must be of type {1}, found {2}")]
+    InvalidSettingType(String, String, String, PathBuf),
+
+    #[error("setting {0:?} in profile {1:?} is a datetime, which modules can't use yet, use a string instead")]
+    DatetimeSetting(String, PathBuf),
 
-    #[error("error in rhai module {0:?}\n{1}")]
-    RhaiModuleError(PathBuf, Box<dyn std::error::Error>),
+    #[error("setting {0:?} in profile {3:?} has value {1}, expected one of {2}")]
+    SettingNotAllowed(String, String, String, PathBuf),
+
+    #[error(
+        "setting {0:?} is required by the schema of profile {1:?} but has no value or default"
+    )]
+    MissingSetting(String, PathBuf),
+
+    #[error("setting {0:?} is not declared in the schema of profile {1:?}")]
+    UnknownSetting(String, PathBuf),
+
+    #[error("template {1:?} uses undefined partial {0:?}")]
+    UndefinedPartial(String, PathBuf),
+
+    #[error("template {1:?} uses helper {0:?} which is not registered")]
+    UnknownHelper(String, PathBuf),
+
+    #[error("template {1:?} uses {{{{{0}}}}}, which calls the {0:?} helper rather than looking up the variable, use {{{{this.{0}}}}}")]
+    BareHelperCall(String, PathBuf),
+
+    #[error("{0} {1:?} looks unused in profile {2:?}: no module mentions its name as a string and no template includes it, names built at runtime aren't seen")]
+    UnusedTemplate(String, PathBuf, PathBuf),
+
+    #[error("found {0} problem(s) in profile {1:?}")]
+    CheckFailed(usize, PathBuf),
+
+    #[error("found {} error(s) in the modules and templates of profile {1:?}", .0.len())]
+    ProfileErrors(Vec<Error>, PathBuf),
+
+    #[error("{} of the profiles being applied failed", .0.len())]
+    ProfilesFailed(Vec<Error>),
+
+    #[error("setting {0:?} is not declared by any of the profiles being applied")]
+    UnknownOverride(String),
+
+    #[error("{0} target(s) of profile {1:?} don't match their expected files")]
+    TestFailed(usize, PathBuf),
 
     #[error("there is no setting with the name {0:?} in the profile {1:?}")]
     SettingNotFound(String, PathBuf),
 }
+
+impl Error {
+    /// The name of the variant, stable enough for scripts to match on.
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Error::NoConfigDir => "NoConfigDir",
+            Error::NoEmptyFileName => "NoEmptyFileName",
+            Error::NotFound(..) => "NotFound",
+            Error::AlreadyExists(..) => "AlreadyExists",
+            Error::NotInProfile(..) => "NotInProfile",
+            Error::UsedProfVal(..) => "UsedProfVal",
+            Error::DupeProfVal(..) => "DupeProfVal",
+            Error::UneditableProfile(..) => "UneditableProfile",
+            Error::TemplateTwice(..) => "TemplateTwice",
+            Error::RelativeTarget(..) => "RelativeTarget",
+            Error::TemplateTwiceAcrossProfiles(..) => "TemplateTwiceAcrossProfiles",
+            Error::InvalidColor(..) => "InvalidColor",
+            Error::InvalidThemeColor(..) => "InvalidThemeColor",
+            Error::NoRenderTarget => "NoRenderTarget",
+            Error::NotATerminal => "NotATerminal",
+            Error::TomlDeError(..) => "TomlDeError",
+            Error::TomlSeError(..) => "TomlSeError",
+            Error::TomlEditError(..) => "TomlEditError",
+            Error::IoError(..) => "IoError",
+            Error::JsonError(..) => "JsonError",
+            Error::WatchError(..) => "WatchError",
+            Error::TemplateError(..) => "TemplateError",
+            Error::TemplateSyntaxError(..) => "TemplateSyntaxError",
+            Error::RenderError(..) => "RenderError",
+            Error::TemplateRenderError(..) => "TemplateRenderError",
+            Error::ScriptHelperError(..) => "ScriptHelperError",
+            Error::RhaiModuleError(..) => "RhaiModuleError",
+            Error::InvalidSettingOverride(..) => "InvalidSettingOverride",
+            Error::InvalidSettingType(..) => "InvalidSettingType",
+            Error::SettingNotAllowed(..) => "SettingNotAllowed",
+            Error::MissingSetting(..) => "MissingSetting",
+            Error::DatetimeSetting(..) => "DatetimeSetting",
+            Error::UnknownSetting(..) => "UnknownSetting",
+            Error::UndefinedPartial(..) => "UndefinedPartial",
+            Error::UnknownHelper(..) => "UnknownHelper",
+            Error::BareHelperCall(..) => "BareHelperCall",
+            Error::UnusedTemplate(..) => "UnusedTemplate",
+            Error::CheckFailed(..) => "CheckFailed",
+            Error::ProfileErrors(..) => "ProfileErrors",
+            Error::ProfilesFailed(..) => "ProfilesFailed",
+            Error::UnknownOverride(..) => "UnknownOverride",
+            Error::TestFailed(..) => "TestFailed",
+            Error::SettingNotFound(..) => "SettingNotFound",
+        }
+    }
+
+    /// Every path the error refers to, in the order they appear in the message.
+    pub fn paths(&self) -> Vec<&Path> {
+        match self {
+            Error::NotFound(_, path)
+            | Error::AlreadyExists(_, path)
+            | Error::UneditableProfile(_, path)
+            | Error::TemplateTwice(path)
+            | Error::RelativeTarget(path)
+            | Error::InvalidThemeColor(_, path, _)
+            | Error::InvalidSettingType(_, _, _, path)
+            | Error::SettingNotAllowed(_, _, _, path)
+            | Error::MissingSetting(_, path)
+            | Error::DatetimeSetting(_, path)
+            | Error::UnknownSetting(_, path)
+            | Error::UndefinedPartial(_, path)
+            | Error::UnknownHelper(_, path)
+            | Error::BareHelperCall(_, path)
+            | Error::CheckFailed(_, path)
+            | Error::ProfileErrors(_, path)
+            | Error::TestFailed(_, path)
+            | Error::SettingNotFound(_, path) => vec![path],
+            // the diagnostic may point into an imported module or partial
+            Error::RhaiModuleError(path, diagnostic)
+            | Error::ScriptHelperError(path, diagnostic)
+            | Error::TemplateSyntaxError(path, diagnostic)
+            | Error::TemplateRenderError(path, diagnostic) => match path == &diagnostic.path {
+                true => vec![path],
+                false => vec![path, &diagnostic.path],
+            },
+            Error::TemplateTwiceAcrossProfiles(target, a, b) => vec![target, a, b],
+            Error::NotInProfile(_, a, b)
+            | Error::UsedProfVal(_, a, b)
+            | Error::DupeProfVal(_, a, b)
+            | Error::UnusedTemplate(_, a, b) => vec![a, b],
+            _ => Vec::new(),
+        }
+    }
+}
+
+impl Serialize for Error {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut causes = Vec::new();
+        let mut source = std::error::Error::source(self);
+
+        while let Some(err) = source {
+            causes.push(err.to_string());
+            source = err.source();
+        }
+
+        let mut map = serializer.serialize_map(None)?;
+
+        map.serialize_entry("kind", self.kind())?;
+        map.serialize_entry("message", &self.to_string())?;
+        map.serialize_entry("paths", &self.paths())?;
+        map.serialize_entry("causes", &causes)?;
+
+        if let Error::ProfileErrors(errors, _) | Error::ProfilesFailed(errors) = self {
+            map.serialize_entry("errors", errors)?;
+        }
+
+        map.end()
+    }
+}
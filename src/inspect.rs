@@ -0,0 +1,402 @@
+use crate::cli::Format;
+use reconf::{
+    error::{print_chain, Error},
+    profile::{self, ApplyReport, Outcome, Profile, ProfileData},
+    relative_path::*,
+};
+use serde::Serialize;
+use serde_json::json;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::{
+    env, fs,
+    path::{Path, PathBuf},
+};
+use toml::Value;
+
+const COMPONENT_DIRS: [Dir; 4] = [Dir::Modules, Dir::Helpers, Dir::Templates, Dir::Partials];
+
+fn load_profile(path: &PathBuf) -> Result<ProfileData, Error> {
+    Ok(toml::from_str(&fs::read_to_string(path)?)?)
+}
+
+/// Maps every resolved component path to the names of the profiles that reference it.
+fn references() -> Result<HashMap<PathBuf, Vec<String>>, Error> {
+    let mut references: HashMap<PathBuf, Vec<String>> = HashMap::new();
+
+    for profile in Dir::Profiles.list()? {
+        let path = RelativePath::from(profile.to_path_buf()).resolve(Dir::Profiles, "toml")?;
+        let name = profile.with_extension("").to_string_lossy().into_owned();
+        let data = match load_profile(&path) {
+            Ok(data) => data,
+            Err(err) => {
+                eprintln!("WARNING! Skipping profile {:?}\n{}", path, err);
+                continue;
+            }
+        };
+
+        for dir in COMPONENT_DIRS {
+            for component in data.components(dir) {
+                let component = RelativePath::from(component.to_path_buf())
+                    .resolve_unchecked(dir, dir.ext_str())?;
+
+                references.entry(component).or_default().push(name.clone());
+            }
+        }
+    }
+
+    Ok(references)
+}
+
+/// Names the components of a directory, profiles and themes are named without their extension.
+fn names(dir: Dir) -> Result<Vec<String>, Error> {
+    Ok(dir
+        .list()?
+        .into_iter()
+        .map(|component| match dir {
+            Dir::Profiles | Dir::Themes => component.with_extension(""),
+            _ => component,
+        })
+        .map(|component| component.display().to_string())
+        .collect())
+}
+
+pub fn list(dir: Option<Dir>, format: Format) -> Result<(), Error> {
+    let dirs = match dir {
+        Some(dir) => vec![dir],
+        None => vec![
+            Dir::Profiles,
+            Dir::Modules,
+            Dir::Helpers,
+            Dir::Templates,
+            Dir::Partials,
+            Dir::Themes,
+        ],
+    };
+
+    if format == Format::Json {
+        let listed = dirs
+            .iter()
+            .map(|dir| Ok((dir.path_str(), names(*dir)?)))
+            .collect::<Result<BTreeMap<_, _>, Error>>()?;
+
+        println!("{}", serde_json::to_string_pretty(&listed)?);
+        return Ok(());
+    }
+
+    for (idx, dir) in dirs.iter().enumerate() {
+        // only print headers when listing everything
+        if dirs.len() > 1 {
+            if idx > 0 {
+                println!();
+            }
+
+            println!("{}s:", dir.component_str());
+        }
+
+        for name in names(*dir)? {
+            println!("{}", name);
+        }
+    }
+
+    Ok(())
+}
+
+#[derive(Serialize)]
+struct ShownComponent {
+    path: PathBuf,
+    missing: bool,
+    also_used_by: Vec<String>,
+}
+
+#[derive(Serialize)]
+struct Shown {
+    name: String,
+    path: PathBuf,
+    theme: Option<ShownComponent>,
+    components: BTreeMap<String, Vec<ShownComponent>>,
+    settings: BTreeMap<String, Value>,
+}
+
+impl ShownComponent {
+    fn resolve(component: &Path, dir: Dir) -> Result<Self, Error> {
+        let (path, missing) =
+            match RelativePath::from(component.to_path_buf()).resolve(dir, dir.ext_str()) {
+                Ok(path) => (path, false),
+                Err(Error::NotFound(_, path)) => (path, true),
+                Err(err) => return Err(err),
+            };
+
+        Ok(Self {
+            path,
+            missing,
+            also_used_by: Vec::new(),
+        })
+    }
+}
+
+pub fn show(profile: RelativePath, format: Format) -> Result<(), Error> {
+    let path = profile.resolve(Dir::Profiles, "toml")?;
+    let data = load_profile(&path)?;
+    let references = references()?;
+    let name = profile.path().with_extension("");
+    let mut components = BTreeMap::new();
+
+    for dir in COMPONENT_DIRS {
+        let mut shown = Vec::new();
+
+        for component in data.components(dir) {
+            let mut component = ShownComponent::resolve(component, dir)?;
+
+            component.also_used_by = references
+                .get(&component.path)
+                .into_iter()
+                .flatten()
+                .filter(|other| PathBuf::from(other) != name)
+                .cloned()
+                .collect();
+
+            shown.push(component);
+        }
+
+        components.insert(dir.path_str().to_string(), shown);
+    }
+
+    let shown = Shown {
+        theme: match &data.theme {
+            Some(theme) => Some(ShownComponent::resolve(theme, Dir::Themes)?),
+            None => None,
+        },
+        name: data.inner.name,
+        path,
+        components,
+        settings: data.settings,
+    };
+
+    if format == Format::Json {
+        println!("{}", serde_json::to_string_pretty(&shown)?);
+        return Ok(());
+    }
+
+    println!("profile {} ({:?})", shown.name, shown.path);
+
+    if let Some(theme) = &shown.theme {
+        match theme.missing {
+            true => println!("theme {:?} (missing)", theme.path),
+            false => println!("theme {:?}", theme.path),
+        }
+    }
+
+    for dir in COMPONENT_DIRS {
+        println!("\n{}s:", dir.component_str());
+
+        for component in &shown.components[dir.path_str()] {
+            if component.missing {
+                println!("  {:?} (missing)", component.path);
+            } else if component.also_used_by.is_empty() {
+                println!("  {:?}", component.path);
+            } else {
+                println!(
+                    "  {:?} (also used by {})",
+                    component.path,
+                    component.also_used_by.join(", ")
+                );
+            }
+        }
+    }
+
+    println!("\nsettings:");
+
+    for (name, value) in &shown.settings {
+        println!("  {} = {}", name, value);
+    }
+
+    Ok(())
+}
+
+/// Prints every problem in a profile, failing if there were any.
+pub fn check(profile: RelativePath, format: Format) -> Result<(), Error> {
+    let path = profile.resolve(Dir::Profiles, "toml")?;
+    let problems = profile::check(profile)?;
+
+    match format {
+        Format::Json => println!("{}", json!({ "problems": problems })),
+        Format::Text => problems.iter().for_each(|problem| print_chain(problem)),
+    }
+
+    match problems.len() {
+        0 => Ok(()),
+        count => Err(Error::CheckFailed(count, path)),
+    }
+}
+
+/// Prints the selected targets of a profile to stdout instead of writing them.
+///
+/// Headers go to stderr so a single rendered target can be piped or diffed directly.
+pub fn render(
+    mut profile: Profile,
+    template: Option<String>,
+    target: Option<PathBuf>,
+    data: bool,
+    format: Format,
+) -> Result<(), Error> {
+    // relative targets are resolved from the working directory like any other argument
+    let target = match target {
+        Some(target) if target.is_relative() => Some(env::current_dir()?.join(target)),
+        target => target,
+    };
+
+    let rendered = profile.render(|path, name| {
+        template.as_ref().is_none_or(|template| name == template)
+            && target.as_ref().is_none_or(|target| path == target)
+    })?;
+
+    if rendered.is_empty() {
+        return Err(Error::NoRenderTarget);
+    }
+
+    if format == Format::Json {
+        let targets = rendered
+            .iter()
+            .map(|rendered| {
+                let mut target = json!({
+                    "path": rendered.target,
+                    "template": rendered.template,
+                    "output": rendered.output,
+                });
+
+                if data {
+                    target["data"] = rendered.data.clone();
+                }
+
+                target
+            })
+            .collect::<Vec<_>>();
+
+        println!("{}", json!({ "targets": targets }));
+        return Ok(());
+    }
+
+    for rendered in rendered {
+        if data {
+            eprintln!(
+                "==> data for {:?} (template {:?}) <==",
+                rendered.target, rendered.template
+            );
+            println!("{}", serde_json::to_string_pretty(&rendered.data)?);
+        }
+
+        eprintln!(
+            "==> {:?} (template {:?}) <==",
+            rendered.target, rendered.template
+        );
+        print!("{}", rendered.output);
+    }
+
+    Ok(())
+}
+
+/// Prints the files put back from the backup, text mode stays quiet like a successful apply.
+pub fn restored(restored: &[PathBuf], format: Format) {
+    if format == Format::Json {
+        println!("{}", json!({ "restored": restored }));
+    }
+}
+
+/// Prints an error along with its causes.
+pub fn error(err: &Error, format: Format) {
+    match format {
+        Format::Json => println!("{}", json!({ "error": err })),
+        Format::Text => print_chain(err),
+    }
+}
+
+/// Prints the outcome of an apply, only failed writes are reported in text mode.
+pub fn report(report: &ApplyReport, format: Format) {
+    if format == Format::Json {
+        let backed_up = report.backed_up.iter().collect::<HashSet<_>>();
+        let written = report.written.iter().map(|(path, bytes)| {
+            json!({
+                "path": path,
+                "status": "written",
+                "bytes": bytes,
+                "backed_up": backed_up.contains(path),
+            })
+        });
+        let failed = report.failed.iter().map(|(path, err)| {
+            json!({
+                "path": path,
+                "status": "failed",
+                "error": err.to_string(),
+                "backed_up": backed_up.contains(path),
+            })
+        });
+
+        println!(
+            "{}",
+            json!({
+                "targets": written.chain(failed).collect::<Vec<_>>(),
+                "backup": report.backup,
+                "after_template": report.after_template,
+            })
+        );
+        return;
+    }
+
+    for (path, err) in &report.failed {
+        eprintln!("WARNING! Failed to write config file {:?}\n{}", path, err);
+    }
+}
+
+/// Prints the result of every target in a profile test, failing if any didn't match.
+pub fn test(profile: RelativePath, update: bool, format: Format) -> Result<(), Error> {
+    let path = profile.resolve(Dir::Profiles, "toml")?;
+    let results = profile::test(profile, update)?;
+
+    match format {
+        Format::Json => {
+            let results = results
+                .iter()
+                .map(|result| {
+                    let (outcome, line) = match result.outcome {
+                        Outcome::Passed => ("passed", None),
+                        Outcome::Changed(line) => ("changed", Some(line)),
+                        Outcome::Missing => ("missing", None),
+                        Outcome::Stale => ("stale", None),
+                        Outcome::Updated => ("updated", None),
+                    };
+
+                    json!({
+                        "target": result.target,
+                        "expected": result.expected,
+                        "outcome": outcome,
+                        "line": line,
+                    })
+                })
+                .collect::<Vec<_>>();
+
+            println!("{}", json!({ "results": results }));
+        }
+        Format::Text => {
+            for result in &results {
+                match result.outcome {
+                    Outcome::Passed => println!("ok       {:?}", result.target),
+                    Outcome::Changed(line) => println!(
+                        "changed  {:?} (line {}, expected {:?})",
+                        result.target, line, result.expected
+                    ),
+                    Outcome::Missing => println!(
+                        "missing  {:?} (expected {:?})",
+                        result.target, result.expected
+                    ),
+                    Outcome::Stale => println!("stale    {:?}", result.expected),
+                    Outcome::Updated => println!("updated  {:?}", result.expected),
+                }
+            }
+        }
+    }
+
+    match results.iter().filter(|result| result.failed()).count() {
+        0 => Ok(()),
+        count => Err(Error::TestFailed(count, path)),
+    }
+}
@@ -0,0 +1,259 @@
+use crate::{cli, inspect};
+use dialoguer::{Confirm, Input, Select};
+use reconf::{
+    error::{print_chain, Error},
+    profile::{self, ProfileDocument, SettingSchema, SettingType},
+    relative_path::*,
+    ApplyOptions, Profile, Sink,
+};
+use similar::TextDiff;
+use std::{collections::BTreeMap, path::PathBuf};
+use toml::Value;
+
+/// Asks for a new value of a setting with a prompt matching its type.
+///
+/// Settings with allowed values are picked from a list, arrays and tables are entered as TOML.
+fn ask(
+    name: &str,
+    current: Option<&Value>,
+    schema: Option<&SettingSchema>,
+) -> Result<Value, Error> {
+    let allowed = schema
+        .map(|schema| schema.allowed.as_slice())
+        .unwrap_or_default();
+
+    if !allowed.is_empty() {
+        let picked = Select::new()
+            .with_prompt(name)
+            .items(allowed)
+            .default(
+                current
+                    .and_then(|current| allowed.iter().position(|value| value == current))
+                    .unwrap_or_default(),
+            )
+            .interact()?;
+
+        return Ok(allowed[picked].clone());
+    }
+
+    let kind = schema
+        .and_then(|schema| schema.kind)
+        .or_else(|| current.and_then(SettingType::of))
+        .unwrap_or(SettingType::String);
+
+    Ok(match (kind, current) {
+        (SettingType::Boolean, current) => {
+            let mut confirm = Confirm::new();
+
+            if let Some(Value::Boolean(current)) = current {
+                confirm.default(*current);
+            }
+
+            Value::Boolean(confirm.with_prompt(name).interact()?)
+        }
+        (SettingType::Integer, current) => {
+            let mut input = Input::<i64>::new();
+
+            if let Some(Value::Integer(current)) = current {
+                input.default(*current);
+            }
+
+            Value::Integer(input.with_prompt(name).interact_text()?)
+        }
+        (SettingType::Float, current) => {
+            let mut input = Input::<f64>::new();
+
+            if let Some(Value::Float(current)) = current {
+                input.default(*current);
+            }
+
+            Value::Float(input.with_prompt(name).interact_text()?)
+        }
+        (SettingType::String, current) => {
+            let mut input = Input::<String>::new();
+
+            if let Some(Value::String(current)) = current {
+                input.default(current.to_owned());
+            }
+
+            Value::String(input.with_prompt(name).interact_text()?)
+        }
+        (_, current) => {
+            let mut input = Input::<String>::new();
+
+            if let Some(current) = current {
+                input.default(current.to_string());
+            }
+
+            cli::parse_setting_value(
+                &input
+                    .with_prompt(format!("{} (TOML)", name))
+                    .interact_text()?,
+            )?
+        }
+    })
+}
+
+/// Renders the profile with the changed settings, shows what would change and applies it once
+/// confirmed. Returns whether the profile was applied.
+fn preview(
+    profile: &RelativePath,
+    changed: &BTreeMap<String, Value>,
+    options: &ApplyOptions,
+    sink: &mut dyn Sink,
+) -> Result<bool, Error> {
+    let mut opened = Profile::open(profile.clone(), changed.clone())?;
+    let prepared = opened.prepare()?;
+    let mut differences = 0;
+
+    for rendered in &prepared.rendered {
+        let current = sink.read(&rendered.target)?.unwrap_or_default();
+        let current = String::from_utf8_lossy(&current);
+
+        if current == rendered.output {
+            continue;
+        }
+
+        let target = rendered.target.display().to_string();
+
+        differences += 1;
+        print!(
+            "{}",
+            TextDiff::from_lines(current.as_ref(), &rendered.output)
+                .unified_diff()
+                .header(&target, &target)
+        );
+    }
+
+    if differences == 0 {
+        println!("no targets would change");
+    }
+
+    if !Confirm::new()
+        .with_prompt(format!("Apply {} changed target(s)?", differences))
+        .default(differences > 0)
+        .interact()?
+    {
+        return Ok(false);
+    }
+
+    if !changed.is_empty()
+        && Confirm::new()
+            .with_prompt("Save the changed settings to the profile?")
+            .default(true)
+            .interact()?
+    {
+        for (name, value) in changed {
+            profile::set_setting(profile.clone(), name.to_owned(), value.to_owned())?;
+        }
+    }
+
+    let report = opened.write(prepared, options, sink)?;
+
+    inspect::report(&report, cli::Format::Text);
+    Ok(true)
+}
+
+/// Picks a profile, edits its settings and applies it after showing the changes.
+pub fn interactive(options: &ApplyOptions, sink: &mut dyn Sink) -> Result<(), Error> {
+    if !profile::attended() {
+        return Err(Error::NotATerminal);
+    }
+
+    let profiles = Dir::Profiles
+        .list()?
+        .into_iter()
+        .map(|profile| profile.with_extension("").display().to_string())
+        .collect::<Vec<_>>();
+
+    if profiles.is_empty() {
+        return Err(Error::NotFound(
+            "profile".to_string(),
+            Dir::Profiles.as_base()?,
+        ));
+    }
+
+    let picked = Select::new()
+        .with_prompt("Profile")
+        .items(&profiles)
+        .default(0)
+        .interact()?;
+    let profile = RelativePath::from(PathBuf::from(&profiles[picked]));
+    let doc = ProfileDocument::open(&profile)?;
+    let mut changed = BTreeMap::new();
+
+    loop {
+        let mut names = doc.data.settings.keys().collect::<Vec<_>>();
+
+        names.extend(doc.data.schema.keys());
+        names.sort();
+        names.dedup();
+
+        let current = |name: &str| {
+            changed
+                .get(name)
+                .or_else(|| doc.data.settings.get(name))
+                .or_else(|| doc.data.schema.get(name)?.default.as_ref())
+        };
+        let mut items = names
+            .iter()
+            .map(|name| {
+                let description = doc
+                    .data
+                    .schema
+                    .get(*name)
+                    .and_then(|schema| schema.description.as_ref())
+                    .map(|description| format!("  ({})", description))
+                    .unwrap_or_default();
+
+                match (current(name), changed.contains_key(*name)) {
+                    (Some(value), true) => format!("{} = {} (changed){}", name, value, description),
+                    (Some(value), false) => format!("{} = {}{}", name, value, description),
+                    (None, _) => format!("{} is not set{}", name, description),
+                }
+            })
+            .collect::<Vec<_>>();
+
+        items.push("Preview and apply".to_string());
+        items.push("Quit".to_string());
+
+        let picked = Select::new()
+            .with_prompt(format!("Settings of {}", doc.data.inner.name))
+            .items(&items)
+            .default(names.len())
+            .interact()?;
+
+        if picked == names.len() {
+            match preview(&profile, &changed, options, sink) {
+                Ok(true) => return Ok(()),
+                Ok(false) => continue,
+                // broken settings or modules shouldn't end the session
+                Err(err) => {
+                    print_chain(&err);
+                    continue;
+                }
+            }
+        }
+
+        let name = match names.get(picked) {
+            Some(name) => name.to_string(),
+            None => return Ok(()),
+        };
+        let schema = doc.data.schema.get(&name);
+        let checked = ask(&name, current(&name), schema).and_then(|value| {
+            if let Some(schema) = schema {
+                schema.check(&name, &value, &doc.path)?;
+            }
+
+            Ok(value)
+        });
+
+        match checked {
+            Ok(value) => changed.insert(name, value),
+            Err(err) => {
+                print_chain(&err);
+                continue;
+            }
+        };
+    }
+}
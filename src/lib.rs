@@ -0,0 +1,20 @@
+//! Config file templating with Handlebars templates and Rhai modules.
+//!
+//! Profiles are opened from the reconf root (see [`relative_path::root`]), or from any root with
+//! [`Profile::open_in`], after which they can be rendered in memory with [`Profile::render`] or
+//! written out with [`Profile::apply`].
+
+pub mod backup;
+pub mod color;
+pub mod component;
+pub mod diagnostic;
+mod dynamic_module_resolver;
+pub mod error;
+pub mod profile;
+pub mod relative_path;
+pub mod sink;
+
+pub use error::Error;
+pub use profile::{ApplyOptions, ApplyReport, Prepared, Profile, ProfileData, Rendered};
+pub use relative_path::{Dir, RelativePath};
+pub use sink::Sink;
@@ -1,24 +1,57 @@
-use cli::Action::*;
-use profile::Profile;
-use relative_path::Dir;
-use std::{fs::File, io::copy, path::PathBuf};
-use tar::Archive;
+use cli::{Action, Action::*, Format, Global};
+use reconf::sink::{Filesystem, Prefix, Sink};
+use reconf::{backup, component, profile, ApplyOptions, Dir, Error, Profile};
+use std::{collections::BTreeMap, process};
 
 mod cli;
-mod component;
-mod dynamic_module_resolver;
-mod error;
-mod profile;
-mod relative_path;
+mod completions;
+mod inspect;
+mod interactive;
+mod scaffold;
+mod watch;
 
-#[allow(unused_variables)]
 fn main() -> main_error::MainResult {
-    let action = cli::main()?;
+    let (global, action) = cli::main();
+    let format = global.format;
+
+    match action.and_then(|action| run(action, global)) {
+        Ok(()) => Ok(()),
+        // main_error only prints a single chain of causes
+        // the JSON document of a failed check or test already lists what went wrong
+        Err(Error::CheckFailed(..) | Error::TestFailed(..)) if format == Format::Json => {
+            process::exit(1);
+        }
+        Err(err)
+            if format == Format::Json
+                || matches!(err, Error::ProfileErrors(..) | Error::ProfilesFailed(..)) =>
+        {
+            inspect::error(&err, format);
+            process::exit(1);
+        }
+        Err(err) => Err(err.into()),
+    }
+}
+
+#[allow(unused_variables)]
+fn run(action: Action, global: Global) -> Result<(), Error> {
+    let format = global.format;
+    let mut options = ApplyOptions::default();
+    let mut sink: Box<dyn Sink> = match global.root_prefix {
+        Some(prefix) => {
+            // staged configs shouldn't restart anything on the real system
+            options.after_template = false;
+            Box::new(Prefix::new(prefix))
+        }
+        None => Box::new(Filesystem),
+    };
 
     match action {
         AddHelper { profile, helper } => component::add(profile, Dir::Helpers, helper)?,
         AddModule { profile, module } => component::add(profile, Dir::Modules, module)?,
+        AddPartial { profile, partial } => component::add(profile, Dir::Partials, partial)?,
         AddTemplate { profile, template } => component::add(profile, Dir::Templates, template)?,
+        Check { profile } => inspect::check(profile, format)?,
+        Completions { shell } => completions::print(&shell)?,
         ChangeSetting {
             profile,
             name,
@@ -29,22 +62,61 @@ fn main() -> main_error::MainResult {
             name,
             value: None,
         } => profile::rm_setting(profile, name)?,
-        ApplyProfile { profile } => Profile::open(profile)?.apply()?,
-        Nothing => {}
-        Restore => {
-            let file = File::open(Dir::Config.as_base()?.join("backup.tar"))?;
-            let mut archive = Archive::new(file);
+        ApplyProfile {
+            profiles,
+            overrides,
+            watch: false,
+        } => {
+            let report = match profiles.len() {
+                1 => {
+                    Profile::open(profiles[0].clone(), overrides)?.apply(&options, sink.as_mut())?
+                }
+                _ => Profile::apply_all(
+                    &mut Profile::open_all(&profiles, &overrides)?,
+                    &options,
+                    sink.as_mut(),
+                )?,
+            };
 
-            for file in archive.entries()? {
-                let mut file = file?;
-                let path = PathBuf::from("/").join(file.path()?);
-
-                copy(&mut file, &mut File::options().write(true).open(path)?)?;
-            }
+            inspect::report(&report, format);
         }
+        ApplyProfile {
+            profiles,
+            overrides,
+            watch: true,
+        } => watch::watch(profiles, overrides, &options, sink.as_mut(), format)?,
+        Init => scaffold::init(format)?,
+        Interactive => interactive::interactive(&options, sink.as_mut())?,
+        List { dir } => inspect::list(dir, format)?,
+        New { dir, name, profile } => scaffold::new(dir, name, profile, format)?,
+        Nothing => {}
+        Render {
+            profile,
+            overrides,
+            template,
+            target,
+            data,
+        } => inspect::render(
+            Profile::open(profile, overrides)?,
+            template,
+            target,
+            data,
+            format,
+        )?,
+        Restore => inspect::restored(&backup::restore(sink.as_mut())?, format),
         RmHelper { profile, helper } => component::rm(profile, Dir::Helpers, helper)?,
         RmModule { profile, module } => component::rm(profile, Dir::Modules, module)?,
+        RmPartial { profile, partial } => component::rm(profile, Dir::Partials, partial)?,
         RmTemplate { profile, template } => component::rm(profile, Dir::Templates, template)?,
+        SetTheme { profile, theme } => {
+            profile::set_theme(profile.clone(), theme)?;
+
+            let report = Profile::open(profile, BTreeMap::new())?.apply(&options, sink.as_mut())?;
+
+            inspect::report(&report, format);
+        }
+        Show { profile } => inspect::show(profile, format)?,
+        Test { profile, update } => inspect::test(profile, update, format)?,
     }
 
     Ok(())
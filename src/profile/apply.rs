@@ -1,64 +1,196 @@
-use super::Profile;
-use crate::{error::Error, relative_path::*};
-use std::fs::{write, File};
-use std::{collections::HashMap, path::PathBuf};
-use tar::Builder;
+use super::{Profile, Rendered};
+use crate::{backup, diagnostic::Diagnostic, error::Error, sink::Sink};
+use rhai::{Dynamic, EvalAltResult, AST};
+use std::collections::{HashMap, HashSet};
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// What `Profile::apply` does besides writing the targets.
+#[derive(Debug, Clone)]
+pub struct ApplyOptions {
+    /// Archive the targets that are about to be overwritten so they can be restored.
+    pub backup: bool,
+    /// Run the `after_template` functions of the modules once the targets are written.
+    pub after_template: bool,
+}
+
+impl Default for ApplyOptions {
+    fn default() -> Self {
+        Self {
+            backup: true,
+            after_template: true,
+        }
+    }
+}
+
+/// The rendered targets of a profile, written by `Profile::write` once they've been looked over.
+pub struct Prepared {
+    pub rendered: Vec<Rendered>,
+    /// Kept to run `after_template` without evaluating the modules again.
+    modules: Vec<(PathBuf, AST)>,
+}
+
+/// The outcome of `Profile::apply` and `Profile::apply_all`, targets are in path order.
+#[derive(Debug, Default)]
+pub struct ApplyReport {
+    /// Targets that were written along with the number of bytes written.
+    pub written: Vec<(PathBuf, usize)>,
+    /// Targets that could not be written, these don't stop the remaining targets.
+    pub failed: Vec<(PathBuf, io::Error)>,
+    /// Targets that existed before the apply and were added to the backup archive.
+    pub backed_up: Vec<PathBuf>,
+    /// Where the backup archive was written, if one was.
+    pub backup: Option<PathBuf>,
+    /// Modules whose `after_template` function was run.
+    pub after_template: Vec<PathBuf>,
+}
+
+/// Fails with the errors of several profiles, a single error is returned as is.
+pub(super) fn collected_across(mut errors: Vec<Error>) -> Result<(), Error> {
+    match errors.len() {
+        0 => Ok(()),
+        1 => Err(errors.remove(0)),
+        _ => Err(Error::ProfilesFailed(errors)),
+    }
+}
+
+/// Backs up the existing targets to the archive of `root` and writes `rendered` to `sink`,
+/// recording failed writes.
+fn write_targets(
+    root: &Path,
+    rendered: Vec<Rendered>,
+    options: &ApplyOptions,
+    sink: &mut dyn Sink,
+    report: &mut ApplyReport,
+) -> Result<(), Error> {
+    if options.backup {
+        let archive = backup::path_in(root);
+        let targets = rendered
+            .iter()
+            .map(|rendered| rendered.target.as_path())
+            .collect::<Vec<_>>();
+
+        report.backed_up = backup::create(sink, &archive, &targets)?;
+        report.backup = Some(sink.locate(&archive));
+    }
+
+    // write config
+    for Rendered { target, output, .. } in rendered {
+        match sink.write(&target, output.as_bytes()) {
+            Ok(()) => report.written.push((target, output.len())),
+            Err(err) => report.failed.push((target, err)),
+        };
+    }
+
+    Ok(())
+}
 
 impl Profile {
-    pub fn apply(&self) -> Result<(), Error> {
-        let mut rendered_template_map = HashMap::new();
-        let mut compiled_modules = HashMap::new();
+    /// Converts a Rhai error raised while running the module at `path`.
+    pub(super) fn module_error(&self, path: &Path, err: EvalAltResult) -> Error {
+        Error::RhaiModuleError(
+            path.to_path_buf(),
+            Box::new(Diagnostic::rhai(path, err, &self.modules)),
+        )
+    }
+
+    /// Compiles and evaluates every module, filling the template map.
+    ///
+    /// A failing module doesn't stop the others, its error is added to `errors`. The template
+    /// map is cleared first, so a profile can be evaluated more than once.
+    pub(super) fn evaluate(&self, errors: &mut Vec<Error>) -> Vec<(PathBuf, AST)> {
+        let mut compiled_modules = Vec::new();
+
+        self.template_map.clear();
+        let mut broken = HashSet::new();
 
         // compile modules
         for module in &self.modules {
-            compiled_modules.insert(
-                module,
-                self.engine
-                    .compile_file(module.to_path_buf())
-                    .map_err(|err| Error::RhaiModuleError(module.to_path_buf(), err.into()))?,
-            );
+            let stem = module.file_stem().unwrap_or_default().to_string_lossy();
+            let mut ast = match self.engine.compile_file(module.to_path_buf()) {
+                Ok(ast) => ast,
+                Err(err) => {
+                    errors.push(self.module_error(module, *err));
+                    broken.insert(stem.into_owned());
+                    continue;
+                }
+            };
+
+            // the source names the module in errors from functions it defines
+            ast.set_source(stem.as_ref());
+            compiled_modules.push((module.to_path_buf(), ast));
         }
 
         // evaluate modules
-        for (&path, ast) in &compiled_modules {
-            self.engine
-                .eval_ast(ast)
-                .map_err(|err| Error::RhaiModuleError(path.to_path_buf(), err.into()))?;
+        for (path, ast) in &compiled_modules {
+            if let Err(err) = self.engine.eval_ast::<Dynamic>(ast) {
+                match *err {
+                    // importing a module that didn't compile repeats the error reported above
+                    EvalAltResult::ErrorInModule(ref name, ..) if broken.contains(name) => {}
+                    err => errors.push(self.module_error(path, err)),
+                }
+            }
         }
 
-        // generate templates
-        for templating_data_ref in self.template_map.iter() {
-            let path = templating_data_ref.key().to_path_buf();
-            let (name, data) = templating_data_ref.value();
+        compiled_modules
+    }
 
-            rendered_template_map.insert(path, self.registry.render(name, data)?);
+    /// Fails with every error collected while evaluating and rendering, a single error is
+    /// returned as is.
+    pub(super) fn collected(&self, mut errors: Vec<Error>) -> Result<(), Error> {
+        match errors.len() {
+            0 => Ok(()),
+            1 => Err(errors.remove(0)),
+            _ => Err(Error::ProfileErrors(errors, self.path.to_path_buf())),
         }
+    }
+
+    /// Renders a single template with the data a module passed to `template`.
+    pub(super) fn render_template(&mut self, name: &str, data: &Dynamic) -> Result<String, Error> {
+        let escape = self.escapes.get(name).unwrap_or(&self.escape);
 
-        let mut backup = Builder::new(File::create(Dir::Config.as_base()?.join("backup.tar"))?);
+        self.registry.register_escape_fn(escape.func());
 
-        // backup files
-        for (abs_path, _) in rendered_template_map.iter() {
-            if !abs_path.exists() {
-                continue;
+        self.registry.render(name, data).map_err(|err| {
+            // errors in partials report the partial rather than the rendered template
+            let template = err.template_name.as_deref().unwrap_or(name);
+
+            let path = self
+                .templates
+                .iter()
+                .chain(&self.partials)
+                .find(|(n, _)| n == template);
+
+            match path {
+                Some((_, path)) => Error::TemplateRenderError(
+                    path.to_path_buf(),
+                    Box::new(Diagnostic::render(path, err)),
+                ),
+                None => err.into(),
             }
+        })
+    }
 
-            let path = abs_path.components().skip(1).collect::<PathBuf>();
+    /// Evaluates the modules and renders every target without writing anything.
+    ///
+    /// All module and template errors are reported together.
+    pub fn prepare(&mut self) -> Result<Prepared, Error> {
+        let mut errors = Vec::new();
+        let modules = self.evaluate(&mut errors);
+        let rendered = self.render_targets(|_, _| true, &mut errors);
 
-            backup.append_file(&path, &mut File::open(&abs_path)?)?;
-        }
+        self.collected(errors)?;
 
-        // write config
-        for (path, data) in rendered_template_map.into_iter() {
-            match write(&path, data) {
-                Err(err) => {
-                    eprintln!("WARNING! Failed to write config file {:?}\n{}", path, err)
-                }
-                Ok(()) => {}
-            };
-        }
+        Ok(Prepared { rendered, modules })
+    }
 
-        // run after_template functions
-        for (path, mut ast) in compiled_modules {
+    /// Runs the `after_template` functions of the modules that define one.
+    fn after_template(
+        &self,
+        modules: Vec<(PathBuf, AST)>,
+        report: &mut ApplyReport,
+    ) -> Result<(), Error> {
+        for (path, mut ast) in modules {
             if ast
                 .iter_functions()
                 .find(|f| f.name == "after_template" && f.params.len() == 0)
@@ -72,10 +204,108 @@ impl Profile {
                             })
                             .combine(self.engine.compile("after_template()").unwrap()),
                     )
-                    .map_err(|err| Error::RhaiModuleError(path.to_path_buf(), err.into()))?;
+                    .map_err(|err| self.module_error(&path, *err))?;
+
+                report.after_template.push(path);
             }
         }
 
         Ok(())
     }
+
+    /// Backs up the existing targets and writes the prepared targets to `sink`.
+    ///
+    /// Targets that fail to write are reported rather than aborting the apply.
+    pub fn write(
+        &mut self,
+        prepared: Prepared,
+        options: &ApplyOptions,
+        sink: &mut dyn Sink,
+    ) -> Result<ApplyReport, Error> {
+        let mut report = ApplyReport::default();
+
+        write_targets(&self.root, prepared.rendered, options, sink, &mut report)?;
+
+        if options.after_template {
+            self.after_template(prepared.modules, &mut report)?;
+        }
+
+        Ok(report)
+    }
+
+    /// Evaluates the modules, backs up the existing targets and writes the rendered templates to
+    /// `sink`, see `prepare` and `write`.
+    ///
+    /// Every template is rendered before anything is written, so an error in a module or
+    /// template leaves the targets untouched.
+    pub fn apply(
+        &mut self,
+        options: &ApplyOptions,
+        sink: &mut dyn Sink,
+    ) -> Result<ApplyReport, Error> {
+        let prepared = self.prepare()?;
+
+        self.write(prepared, options, sink)
+    }
+
+    /// Applies several profiles as one, with a single backup of every target and the
+    /// `after_template` functions run once all targets are written.
+    ///
+    /// Nothing is written if a profile fails or two profiles template the same target.
+    pub fn apply_all(
+        profiles: &mut [Profile],
+        options: &ApplyOptions,
+        sink: &mut dyn Sink,
+    ) -> Result<ApplyReport, Error> {
+        let mut prepared = Vec::new();
+        let mut errors = Vec::new();
+
+        // every profile is prepared so all their errors are reported together
+        for profile in profiles.iter_mut() {
+            match profile.prepare() {
+                Ok(profile) => prepared.push(profile),
+                Err(err) => errors.push(err),
+            }
+        }
+
+        collected_across(errors)?;
+
+        let mut owners = HashMap::new();
+
+        for (profile, prepared) in profiles.iter().zip(&prepared) {
+            for rendered in &prepared.rendered {
+                if let Some(owner) = owners.insert(&rendered.target, &profile.path) {
+                    return Err(Error::TemplateTwiceAcrossProfiles(
+                        rendered.target.to_path_buf(),
+                        owner.to_path_buf(),
+                        profile.path.to_path_buf(),
+                    ));
+                }
+            }
+        }
+
+        let mut report = ApplyReport::default();
+        let mut rendered = Vec::new();
+        let mut modules = Vec::new();
+
+        for prepared in prepared {
+            rendered.extend(prepared.rendered);
+            modules.push(prepared.modules);
+        }
+
+        rendered.sort_by(|a, b| a.target.cmp(&b.target));
+
+        // the backup belongs to the root of the first profile
+        if let Some(first) = profiles.first() {
+            write_targets(&first.root, rendered, options, sink, &mut report)?;
+        }
+
+        if options.after_template {
+            for (profile, modules) in profiles.iter().zip(modules) {
+                profile.after_template(modules, &mut report)?;
+            }
+        }
+
+        Ok(report)
+    }
 }
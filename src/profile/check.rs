@@ -0,0 +1,303 @@
+use super::{engine, helpers, schema, Profile, ProfileData, Theme};
+use crate::{diagnostic::Diagnostic, error::Error, relative_path::*};
+use handlebars::template::{Parameter, Template, TemplateElement};
+use std::collections::{BTreeMap, HashSet};
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+/// Helpers registered by `Handlebars::new()`, see `helpers::HELPERS` for reconf's own.
+const BUILTIN_HELPERS: [&str; 17] = [
+    "if", "unless", "each", "with", "lookup", "raw", "log", "eq", "ne", "gt", "gte", "lt", "lte",
+    "and", "or", "not", "len",
+];
+
+#[derive(Default)]
+struct TemplateRefs {
+    helpers: HashSet<String>,
+    bare_names: HashSet<String>,
+    partials: HashSet<String>,
+    inline_partials: HashSet<String>,
+}
+
+fn file_stem(path: &Path) -> Result<String, Error> {
+    Ok(path
+        .file_stem()
+        .ok_or(Error::NoEmptyFileName)?
+        .to_string_lossy()
+        .into_owned())
+}
+
+fn collect_param(param: &Parameter, refs: &mut TemplateRefs) {
+    if let Parameter::Subexpression(subexpr) = param {
+        collect_element(subexpr.as_element(), refs);
+    }
+}
+
+fn collect_element(element: &TemplateElement, refs: &mut TemplateRefs) {
+    match element {
+        TemplateElement::Expression(helper)
+        | TemplateElement::HtmlExpression(helper)
+        | TemplateElement::HelperBlock(helper) => {
+            // a bare `{{name}}` calls the helper of that name if there is one and is a variable
+            // lookup otherwise, so it can't be unknown
+            let is_call = helper.block || !helper.params.is_empty() || !helper.hash.is_empty();
+
+            match (is_call, helper.name.as_name()) {
+                (true, Some(name)) => refs.helpers.insert(name.to_owned()),
+                (false, Some(name)) => refs.bare_names.insert(name.to_owned()),
+                (_, None) => false,
+            };
+
+            helper
+                .params
+                .iter()
+                .chain(helper.hash.values())
+                .for_each(|param| collect_param(param, refs));
+
+            for template in helper.template.iter().chain(helper.inverse.iter()) {
+                collect_template(template, refs);
+            }
+        }
+        TemplateElement::PartialExpression(partial) | TemplateElement::PartialBlock(partial) => {
+            if let Some(name) = partial.name.as_name() {
+                refs.partials.insert(name.to_owned());
+            }
+
+            if let Some(template) = &partial.template {
+                collect_template(template, refs);
+            }
+        }
+        TemplateElement::DecoratorExpression(decorator)
+        | TemplateElement::DecoratorBlock(decorator) => {
+            if decorator.name.as_name() == Some("inline") {
+                if let Some(Parameter::Literal(name)) = decorator.params.first() {
+                    refs.inline_partials
+                        .insert(name.as_str().unwrap_or_default().to_owned());
+                }
+            }
+
+            if let Some(template) = &decorator.template {
+                collect_template(template, refs);
+            }
+        }
+        TemplateElement::RawString(_) | TemplateElement::Comment(_) => {}
+    }
+}
+
+fn collect_template(template: &Template, refs: &mut TemplateRefs) {
+    for element in &template.elements {
+        collect_element(element, refs);
+    }
+}
+
+/// Resolves the components of a profile, recording missing and duplicate components.
+fn resolve_components(
+    root: &Path,
+    data: &ProfileData,
+    dir: Dir,
+    profile: &Path,
+    problems: &mut Vec<Error>,
+) -> Vec<PathBuf> {
+    let mut used = HashSet::new();
+    let mut found = Vec::new();
+
+    for component in data.components(dir) {
+        let component = match RelativePath::from(component.to_path_buf()).resolve_in(
+            root,
+            dir,
+            dir.ext_str(),
+        ) {
+            Ok(component) => component,
+            Err(err) => {
+                problems.push(err);
+                continue;
+            }
+        };
+
+        if !used.insert(component.to_path_buf()) {
+            problems.push(Error::DupeProfVal(
+                dir.component_str().to_string(),
+                component,
+                profile.to_path_buf(),
+            ));
+            continue;
+        }
+
+        found.push(component);
+    }
+
+    found
+}
+
+/// Validates a profile without applying it, returning every problem found.
+///
+/// Only errors that prevent checking at all, like a missing or unparsable profile, are returned
+/// as `Err`.
+pub fn check(profile: RelativePath) -> Result<Vec<Error>, Error> {
+    check_in(&root()?, profile)
+}
+
+/// Like `check` with the profiles and components of `root` rather than the global root.
+pub fn check_in(root: &Path, profile: RelativePath) -> Result<Vec<Error>, Error> {
+    let path = profile.resolve_in(root, Dir::Profiles, "toml")?;
+    let mut data: ProfileData = toml::from_str(&fs::read_to_string(&path)?)?;
+    let mut problems = Vec::new();
+
+    if let Err(err) = schema::validate(&data.schema, &mut data.settings, &path) {
+        problems.push(err);
+    }
+
+    let modules = resolve_components(root, &data, Dir::Modules, &path, &mut problems);
+    let helpers = resolve_components(root, &data, Dir::Helpers, &path, &mut problems);
+    let templates = resolve_components(root, &data, Dir::Templates, &path, &mut problems);
+    let partial_files = resolve_components(root, &data, Dir::Partials, &path, &mut problems);
+    let theme = match &data.theme {
+        Some(theme) => match Theme::open_in(root, &RelativePath::from(theme.to_path_buf())) {
+            Ok(theme) => Some(theme),
+            Err(err) => {
+                problems.push(err);
+                None
+            }
+        },
+        None => None,
+    };
+    let palette = match &theme {
+        Some((path, theme)) => match theme.palette(path) {
+            Ok(palette) => Some((theme, palette)),
+            Err(err) => {
+                problems.push(err);
+                None
+            }
+        },
+        None => None,
+    };
+    let (engine, _) = engine::build(
+        &path,
+        &modules,
+        data.settings.clone(),
+        palette,
+        &data.sandbox,
+    )?;
+    let mut module_sources = Vec::new();
+
+    // compile modules and helpers
+    for module in &modules {
+        module_sources.push(fs::read_to_string(module)?);
+
+        if let Err(err) = engine.compile_file(module.to_path_buf()) {
+            problems.push(Error::RhaiModuleError(
+                module.to_path_buf(),
+                Box::new(Diagnostic::rhai(module, *err, &modules)),
+            ));
+        }
+    }
+
+    for helper in &helpers {
+        if let Err(err) = engine.compile_file(helper.to_path_buf()) {
+            problems.push(Error::ScriptHelperError(
+                helper.to_path_buf(),
+                Box::new(Diagnostic::rhai(helper, *err, &[])),
+            ));
+        }
+    }
+
+    // parse templates and check what they reference
+    let mut known_helpers = BUILTIN_HELPERS
+        .iter()
+        .map(|helper| helper.to_string())
+        .collect::<HashSet<_>>();
+    let mut template_names = HashSet::new();
+    let mut partials = HashSet::new();
+    let mut parsed = Vec::new();
+
+    if data.builtin_helpers {
+        known_helpers.extend(helpers::HELPERS.iter().map(|(name, _)| name.to_string()));
+    }
+
+    for helper in &helpers {
+        known_helpers.insert(file_stem(helper)?);
+    }
+
+    for (dir, files) in [
+        (Dir::Templates, &templates),
+        (Dir::Partials, &partial_files),
+    ] {
+        for file in files {
+            let name = dir.component_name_in(root, file)?;
+
+            template_names.insert(name.clone());
+
+            match Template::compile_with_name(fs::read_to_string(file)?, name.clone()) {
+                Ok(compiled) => parsed.push((dir, file, name, compiled)),
+                Err(err) => problems.push(Error::TemplateSyntaxError(
+                    file.to_path_buf(),
+                    Box::new(Diagnostic::template(file, &err)),
+                )),
+            }
+        }
+    }
+
+    for (_, template, _, compiled) in &parsed {
+        let mut refs = TemplateRefs::default();
+
+        collect_template(compiled, &mut refs);
+
+        for helper in refs.helpers.difference(&known_helpers) {
+            problems.push(Error::UnknownHelper(
+                helper.to_owned(),
+                template.to_path_buf(),
+            ));
+        }
+
+        // reconf's built-ins fall back to the variable, the ones of Handlebars fail without params
+        for name in &refs.bare_names {
+            if BUILTIN_HELPERS.contains(&name.as_str()) {
+                problems.push(Error::BareHelperCall(
+                    name.to_owned(),
+                    template.to_path_buf(),
+                ));
+            }
+        }
+
+        for partial in &refs.partials {
+            if !template_names.contains(partial) && !refs.inline_partials.contains(partial) {
+                problems.push(Error::UndefinedPartial(
+                    partial.to_owned(),
+                    template.to_path_buf(),
+                ));
+            }
+        }
+
+        partials.extend(refs.partials);
+    }
+
+    // a heuristic rather than evaluating the modules, which could prompt or run commands: templates
+    // are used if a module has their name as a string literal or another template includes them
+    for (dir, template, name, _) in &parsed {
+        let quoted = [format!("\"{}\"", name), format!("`{}`", name)];
+        let used = partials.contains(name)
+            || module_sources
+                .iter()
+                .any(|source| quoted.iter().any(|quoted| source.contains(quoted)));
+
+        if !used {
+            problems.push(Error::UnusedTemplate(
+                dir.component_str().to_string(),
+                template.to_path_buf(),
+                path.to_path_buf(),
+            ));
+        }
+    }
+
+    // only catch registration errors once everything above is known to be fine
+    if problems.is_empty() {
+        match Profile::open_in(root, profile, BTreeMap::new()) {
+            Ok(mut opened) => problems.extend(opened.register()),
+            Err(err) => problems.push(err),
+        }
+    }
+
+    Ok(problems)
+}
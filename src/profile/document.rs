@@ -0,0 +1,337 @@
+use super::ProfileData;
+use crate::{error::Error, relative_path::*};
+use std::fs;
+use std::path::{Path, PathBuf};
+use toml::Value;
+use toml_edit::{Array, Document, InlineTable, Item, RawString, Table, TableLike};
+
+/// A profile TOML file that can be edited without losing comments or formatting.
+///
+/// `data` is deserialized from the same source as `doc` and is used for lookups and validation,
+/// all edits go through `doc` so that saving only changes what was edited.
+pub struct ProfileDocument {
+    pub path: PathBuf,
+    pub data: ProfileData,
+    doc: Document,
+}
+
+fn to_edit_value(value: &Value) -> toml_edit::Value {
+    match value {
+        Value::String(s) => s.into(),
+        Value::Integer(i) => (*i).into(),
+        Value::Float(f) => (*f).into(),
+        Value::Boolean(b) => (*b).into(),
+        Value::Datetime(dt) => dt
+            .to_string()
+            .parse::<toml_edit::Datetime>()
+            .expect("toml datetimes should round trip")
+            .into(),
+        Value::Array(vec) => vec.iter().map(to_edit_value).collect::<Array>().into(),
+        Value::Table(map) => map
+            .iter()
+            .map(|(key, value)| (key, to_edit_value(value)))
+            .collect::<InlineTable>()
+            .into(),
+    }
+}
+
+fn raw(raw: Option<&RawString>) -> &str {
+    raw.and_then(RawString::as_str).unwrap_or_default()
+}
+
+/// Splits the text after an array element into the comment on its line and the line break
+/// before the next line.
+fn split_comment(text: &str) -> (&str, &str) {
+    text.split_at(text.rfind('\n').unwrap_or(text.len()))
+}
+
+impl ProfileDocument {
+    pub fn open(profile: &RelativePath) -> Result<Self, Error> {
+        Self::open_in(&root()?, profile)
+    }
+
+    /// Like `open` with the profiles of `root` rather than the global root.
+    pub fn open_in(root: &Path, profile: &RelativePath) -> Result<Self, Error> {
+        let path = profile.resolve_in(root, Dir::Profiles, "toml")?;
+        let buf = fs::read_to_string(&path)?;
+
+        Ok(Self {
+            data: toml::from_str(&buf)?,
+            doc: buf.parse()?,
+            path,
+        })
+    }
+
+    pub fn save(&self) -> Result<(), Error> {
+        fs::write(&self.path, self.doc.to_string())?;
+        Ok(())
+    }
+
+    /// The `[profile]` table, which may also be written inline.
+    fn profile_mut(&mut self) -> Result<&mut dyn TableLike, Error> {
+        self.doc["profile"]
+            .as_table_like_mut()
+            .ok_or_else(|| Error::UneditableProfile("profile".to_string(), self.path.to_owned()))
+    }
+
+    /// Adds `key` to the end of the profile table, or replaces its value keeping its comments.
+    fn set_profile_value(&mut self, key: &str, mut value: toml_edit::Value) -> Result<(), Error> {
+        if let Some(old) = self.profile_mut()?.get(key).and_then(Item::as_value) {
+            *value.decor_mut() = old.decor().clone();
+        }
+
+        // the space before the closing brace of an inline table belongs to its last value
+        if let Some(inline) = self.doc["profile"].as_inline_table_mut() {
+            if !inline.contains_key(key) {
+                if let Some((_, last)) = inline.iter_mut().last() {
+                    let suffix = raw(last.decor().suffix()).to_owned();
+
+                    last.decor_mut().set_suffix("");
+                    value.decor_mut().set_suffix(suffix);
+                }
+            }
+        }
+
+        self.profile_mut()?.insert(key, Item::Value(value));
+        Ok(())
+    }
+
+    /// The component list of `dir`, added to the profile if it doesn't have one yet.
+    fn components_mut(&mut self, dir: Dir) -> Result<&mut Array, Error> {
+        let path = self.path.to_owned();
+
+        if !self.profile_mut()?.contains_key(dir.path_str()) {
+            self.set_profile_value(dir.path_str(), Array::new().into())?;
+        }
+
+        self.profile_mut()?
+            .get_mut(dir.path_str())
+            .and_then(Item::as_array_mut)
+            .ok_or_else(|| Error::UneditableProfile(format!("profile.{}", dir.path_str()), path))
+    }
+
+    pub fn push_component(&mut self, dir: Dir, component: &RelativePath) -> Result<(), Error> {
+        let array = self.components_mut(dir)?;
+        let mut value = toml_edit::Value::from(component.path().to_string_lossy().as_ref());
+        let trailing_comma = array.trailing_comma();
+        let trailing = raw(Some(array.trailing())).to_owned();
+        let last = array
+            .len()
+            .checked_sub(1)
+            .and_then(|idx| array.get_mut(idx));
+
+        // follow the layout of the last element, a comment after it stays with it: that comment
+        // is in the array's trailing text after a trailing comma and in the element's suffix
+        // otherwise
+        if let Some(last) = last {
+            let prefix = raw(last.decor().prefix()).to_owned();
+            let suffix = raw(last.decor().suffix()).to_owned();
+
+            match prefix.rfind('\n') {
+                Some(indent) if trailing_comma => {
+                    let (comment, closing) = split_comment(&trailing);
+
+                    value = value.decorated(format!("{}{}", comment, &prefix[indent..]), "");
+                    array.set_trailing(closing);
+                }
+                Some(indent) => {
+                    let (comment, closing) = split_comment(&suffix);
+
+                    value = value.decorated(format!("{}{}", comment, &prefix[indent..]), closing);
+                    last.decor_mut().set_suffix("");
+                }
+                None => {
+                    value = value.decorated(" ", suffix);
+                    last.decor_mut().set_suffix("");
+                }
+            }
+        }
+
+        array.push_formatted(value);
+        Ok(())
+    }
+
+    pub fn remove_component(&mut self, dir: Dir, idx: usize) -> Result<(), Error> {
+        let array = self.components_mut(dir)?;
+        let removed = array.remove(idx);
+
+        // keep the new first element from inheriting the separator whitespace
+        if idx == 0 {
+            if let Some(first) = array.get_mut(0) {
+                *first.decor_mut() = removed.decor().clone();
+            }
+        }
+
+        Ok(())
+    }
+
+    pub fn set_theme(&mut self, theme: &RelativePath) -> Result<(), Error> {
+        self.set_profile_value("theme", theme.path().to_string_lossy().as_ref().into())
+    }
+
+    pub fn set_setting(&mut self, name: &str, value: &Value) {
+        let settings = self
+            .doc
+            .entry("settings")
+            .or_insert(Item::Table(Table::new()));
+        let mut value = to_edit_value(value);
+
+        // keep comments attached to a setting that is being overwritten
+        if let Some(old) = settings.get(name).and_then(Item::as_value) {
+            *value.decor_mut() = old.decor().clone();
+        }
+
+        settings[name] = Item::Value(value);
+    }
+
+    pub fn rm_setting(&mut self, name: &str) {
+        if let Some(settings) = self.doc.get_mut("settings") {
+            if let Some(settings) = settings.as_table_like_mut() {
+                settings.remove(name);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn document(source: &str) -> ProfileDocument {
+        ProfileDocument {
+            path: PathBuf::from("test.toml"),
+            data: toml::from_str(source).unwrap(),
+            doc: source.parse().unwrap(),
+        }
+    }
+
+    fn pushed(source: &str, component: &str) -> String {
+        let mut doc = document(source);
+
+        doc.push_component(Dir::Modules, &RelativePath::from(PathBuf::from(component)))
+            .unwrap();
+        doc.doc.to_string()
+    }
+
+    fn removed(source: &str, idx: usize) -> String {
+        let mut doc = document(source);
+
+        doc.remove_component(Dir::Modules, idx).unwrap();
+        doc.doc.to_string()
+    }
+
+    #[test]
+    fn pushes_to_a_missing_list() {
+        assert_eq!(
+            pushed("[profile]\nname = \"a\"\n", "b"),
+            "[profile]\nname = \"a\"\nmodules = [\"b\"]\n"
+        );
+    }
+
+    #[test]
+    fn pushes_to_a_single_line_list() {
+        assert_eq!(
+            pushed("[profile]\nname = \"a\"\nmodules = [\"a\"] # mods\n", "b"),
+            "[profile]\nname = \"a\"\nmodules = [\"a\", \"b\"] # mods\n"
+        );
+        assert_eq!(
+            pushed("[profile]\nname = \"a\"\nmodules = []\n", "b"),
+            "[profile]\nname = \"a\"\nmodules = [\"b\"]\n"
+        );
+    }
+
+    #[test]
+    fn pushes_to_a_multi_line_list() {
+        let source = "[profile]\nname = \"a\"\nmodules = [\n    \"a\", # first\n]\n";
+
+        assert_eq!(
+            pushed(source, "b"),
+            "[profile]\nname = \"a\"\nmodules = [\n    \"a\", # first\n    \"b\",\n]\n"
+        );
+
+        let source = "[profile]\nname = \"a\"\nmodules = [\n    \"a\" # first\n]\n";
+
+        assert_eq!(
+            pushed(source, "b"),
+            "[profile]\nname = \"a\"\nmodules = [\n    \"a\", # first\n    \"b\"\n]\n"
+        );
+    }
+
+    #[test]
+    fn pushes_to_an_inline_profile() {
+        assert_eq!(
+            pushed("profile = { name = \"a\", modules = [\"a\"] }\n", "b"),
+            "profile = { name = \"a\", modules = [\"a\", \"b\"] }\n"
+        );
+        assert_eq!(
+            pushed("profile = { name = \"a\" }\n", "b"),
+            "profile = { name = \"a\", modules = [\"b\"] }\n"
+        );
+    }
+
+    #[test]
+    fn removes_components() {
+        let source = "[profile]\nname = \"a\"\nmodules = [\"a\", \"b\", \"c\"]\n";
+
+        assert_eq!(
+            removed(source, 0),
+            "[profile]\nname = \"a\"\nmodules = [\"b\", \"c\"]\n"
+        );
+        assert_eq!(
+            removed(source, 2),
+            "[profile]\nname = \"a\"\nmodules = [\"a\", \"b\"]\n"
+        );
+        assert_eq!(
+            removed("profile = { name = \"a\", modules = [\"a\"] }\n", 0),
+            "profile = { name = \"a\", modules = [] }\n"
+        );
+    }
+
+    #[test]
+    fn sets_the_theme() {
+        let mut doc = document("[profile]\nname = \"a\"\ntheme = \"old\" # keep\n");
+
+        doc.set_theme(&RelativePath::from(PathBuf::from("new")))
+            .unwrap();
+        assert_eq!(
+            doc.doc.to_string(),
+            "[profile]\nname = \"a\"\ntheme = \"new\" # keep\n"
+        );
+
+        let mut doc = document("profile = { name = \"a\" }\n");
+
+        doc.set_theme(&RelativePath::from(PathBuf::from("new")))
+            .unwrap();
+        assert_eq!(
+            doc.doc.to_string(),
+            "profile = { name = \"a\", theme = \"new\" }\n"
+        );
+    }
+
+    #[test]
+    fn edits_settings() {
+        let mut doc = document("[profile]\nname = \"a\"\n\n[settings]\n# size\nsize = 1 # px\n");
+
+        doc.set_setting("size", &Value::Integer(2));
+        doc.set_setting("list", &Value::Array(vec![Value::Boolean(true)]));
+        assert_eq!(
+            doc.doc.to_string(),
+            "[profile]\nname = \"a\"\n\n[settings]\n# size\nsize = 2 # px\nlist = [true]\n"
+        );
+
+        doc.rm_setting("size");
+        doc.rm_setting("missing");
+        assert_eq!(
+            doc.doc.to_string(),
+            "[profile]\nname = \"a\"\n\n[settings]\nlist = [true]\n"
+        );
+
+        let mut doc = document("profile = { name = \"a\" }\nsettings = { a = 1, b = 2 }\n");
+
+        doc.rm_setting("a");
+        assert_eq!(
+            doc.doc.to_string(),
+            "profile = { name = \"a\" }\nsettings = { b = 2 }\n"
+        );
+    }
+}
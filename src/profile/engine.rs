@@ -1,11 +1,40 @@
+use super::{Sandbox, Theme};
+use crate::color::Color;
 use crate::dynamic_module_resolver::DynamicModuleResolver;
 use crate::error::Error;
 use dashmap::DashMap;
-use rhai::{Array, Dynamic, Engine, Module};
-use std::{collections::BTreeMap, path::PathBuf, process::Command, sync::Arc};
+use dialoguer::{Confirm, Input, Select};
+use rhai::{Array, Dynamic, Engine, EvalAltResult, Map, Module, NativeCallContext, FLOAT, INT};
+use std::io::{self, IsTerminal};
+use std::{
+    collections::BTreeMap,
+    path::{Path, PathBuf},
+    process::Command,
+    sync::Arc,
+};
 use toml::Value;
 
-fn run_command(program: String, args: Option<Array>) {
+fn run_command(
+    ctx: NativeCallContext,
+    sandbox: &Sandbox,
+    program: String,
+    args: Option<Array>,
+) -> Result<(), Box<EvalAltResult>> {
+    if sandbox.isolated {
+        return Ok(());
+    }
+
+    if !sandbox.allows_command(&program) {
+        return Err(Box::new(EvalAltResult::ErrorRuntime(
+            format!(
+                "command {:?} is not allowed by the profile's sandbox",
+                program
+            )
+            .into(),
+            ctx.call_position(),
+        )));
+    }
+
     match Command::new(program)
         .args(
             args.unwrap_or_default()
@@ -13,9 +42,11 @@ fn run_command(program: String, args: Option<Array>) {
                 .map(|arg| arg.to_string())
                 .collect::<Vec<_>>(),
         )
+        // like `print`, keep stdout free for rendered output and `--format json`
+        .stdout(io::stderr())
         .status()
     {
-        _ => {}
+        _ => Ok(()),
     }
 }
 
@@ -25,9 +56,8 @@ fn parse_value(value: Value) -> Option<Dynamic> {
         "integer" => value.as_integer().map(Dynamic::from_int),
         "float" => value.as_float().map(Dynamic::from_float),
         "boolean" => value.as_bool().map(Dynamic::from_bool),
-        "datetime" => {
-            todo!("parsing TOML datetimes is currently not supported, feel free to make a PR")
-        }
+        // rejected by `schema::validate` before the engine is built
+        "datetime" => None,
         "array" => value.as_array().map(|vec| -> Option<Dynamic> {
             Some(Dynamic::from_array(
                 vec.into_iter()
@@ -47,53 +77,290 @@ fn parse_value(value: Value) -> Option<Dynamic> {
     }
 }
 
+fn parse_color(color: &str) -> Result<Color, Box<EvalAltResult>> {
+    Color::parse(color).map_err(|err| err.to_string().into())
+}
+
+fn register_color(engine: &mut Engine) {
+    engine
+        .register_type_with_name::<Color>("Color")
+        .register_fn("color", parse_color)
+        .register_fn("rgb", |r: INT, g: INT, b: INT| {
+            Color::from_rgba8(r as FLOAT, g as FLOAT, b as FLOAT, 1.0)
+        })
+        .register_fn("rgba", |r: INT, g: INT, b: INT, a: FLOAT| {
+            Color::from_rgba8(r as FLOAT, g as FLOAT, b as FLOAT, a)
+        })
+        .register_fn("hsl", |h: FLOAT, s: FLOAT, l: FLOAT| {
+            Color::from_hsla(h, s, l, 1.0)
+        })
+        .register_get("r", |c: &mut Color| c.rgb8()[0] as INT)
+        .register_get("g", |c: &mut Color| c.rgb8()[1] as INT)
+        .register_get("b", |c: &mut Color| c.rgb8()[2] as INT)
+        .register_get("a", |c: &mut Color| c.a)
+        .register_fn("hex", |c: &mut Color| c.to_hex())
+        .register_fn("rgb", |c: &mut Color| c.to_rgb())
+        .register_fn("rgba", |c: &mut Color| c.to_rgba())
+        .register_fn("hsl", |c: &mut Color| c.to_hsl())
+        .register_fn("floats", |c: &mut Color| {
+            c.to_floats()
+                .into_iter()
+                .map(Dynamic::from_float)
+                .collect::<Array>()
+        })
+        .register_fn("ansi256", |c: &mut Color| c.to_ansi256() as INT)
+        .register_fn("lighten", |c: &mut Color, amount: FLOAT| c.lighten(amount))
+        .register_fn("darken", |c: &mut Color, amount: FLOAT| c.darken(amount))
+        .register_fn("mix", |c: &mut Color, other: Color, weight: FLOAT| {
+            c.mix(&other, weight)
+        })
+        .register_fn("alpha", |c: &mut Color, alpha: FLOAT| c.with_alpha(alpha))
+        .register_fn("luminance", |c: &mut Color| c.luminance())
+        .register_fn("contrast", |c: &mut Color, other: Color| c.contrast(&other))
+        .register_fn("readable", |c: &mut Color| c.readable(&[]))
+        .register_fn(
+            "readable",
+            |c: &mut Color, candidates: Array| -> Result<Color, Box<EvalAltResult>> {
+                let candidates = candidates
+                    .into_iter()
+                    .map(|candidate| match candidate.clone().try_cast::<Color>() {
+                        Some(color) => Ok(color),
+                        None => parse_color(&candidate.to_string()),
+                    })
+                    .collect::<Result<Vec<_>, _>>()?;
+
+                Ok(c.readable(&candidates))
+            },
+        )
+        .register_fn("to_string", |c: &mut Color| c.to_hex())
+        .register_fn("to_debug", |c: &mut Color| format!("color(\"{}\")", c));
+}
+
+/// Prompts need someone at a terminal, without one `Select` never returns and `Input` silently
+/// takes its default.
+pub fn attended() -> bool {
+    io::stdin().is_terminal() && io::stderr().is_terminal()
+}
+
+fn prompt_error(ctx: &NativeCallContext, err: io::Error) -> Box<EvalAltResult> {
+    Box::new(EvalAltResult::ErrorRuntime(
+        format!("could not prompt: {}", err).into(),
+        ctx.call_position(),
+    ))
+}
+
+fn check_attended(ctx: &NativeCallContext, isolated: bool) -> Result<(), Box<EvalAltResult>> {
+    let reason = match (isolated, attended()) {
+        (false, true) => return Ok(()),
+        (true, _) => "no default to use while testing",
+        (false, false) => "not a terminal",
+    };
+
+    Err(prompt_error(
+        ctx,
+        io::Error::new(io::ErrorKind::Unsupported, reason),
+    ))
+}
+
+/// Functions asking for values at apply time, prompts are drawn on stderr like `print`.
+///
+/// In an isolated sandbox prompts with a default return it without asking.
+fn register_prompts(engine: &mut Engine, isolated: bool) {
+    engine
+        .register_fn(
+            "prompt",
+            move |ctx: NativeCallContext, text: &str| -> Result<String, Box<EvalAltResult>> {
+                check_attended(&ctx, isolated)?;
+
+                Input::new()
+                    .with_prompt(text)
+                    .interact_text()
+                    .map_err(|err| prompt_error(&ctx, err))
+            },
+        )
+        .register_fn(
+            "prompt",
+            move |ctx: NativeCallContext,
+                  text: &str,
+                  default: String|
+                  -> Result<String, Box<EvalAltResult>> {
+                if isolated {
+                    return Ok(default);
+                }
+
+                check_attended(&ctx, isolated)?;
+
+                Input::new()
+                    .with_prompt(text)
+                    .default(default)
+                    .interact_text()
+                    .map_err(|err| prompt_error(&ctx, err))
+            },
+        )
+        .register_fn(
+            "confirm",
+            move |ctx: NativeCallContext, text: &str| -> Result<bool, Box<EvalAltResult>> {
+                check_attended(&ctx, isolated)?;
+
+                Confirm::new()
+                    .with_prompt(text)
+                    .interact()
+                    .map_err(|err| prompt_error(&ctx, err))
+            },
+        )
+        .register_fn(
+            "confirm",
+            move |ctx: NativeCallContext,
+                  text: &str,
+                  default: bool|
+                  -> Result<bool, Box<EvalAltResult>> {
+                if isolated {
+                    return Ok(default);
+                }
+
+                check_attended(&ctx, isolated)?;
+
+                Confirm::new()
+                    .with_prompt(text)
+                    .default(default)
+                    .interact()
+                    .map_err(|err| prompt_error(&ctx, err))
+            },
+        )
+        .register_fn(
+            "select",
+            move |ctx: NativeCallContext,
+                  text: &str,
+                  items: Array|
+                  -> Result<Dynamic, Box<EvalAltResult>> {
+                check_attended(&ctx, isolated)?;
+
+                if items.is_empty() {
+                    return Err(Box::new(EvalAltResult::ErrorRuntime(
+                        "select needs at least one item".into(),
+                        ctx.call_position(),
+                    )));
+                }
+
+                let picked = Select::new()
+                    .with_prompt(text)
+                    .items(&items.iter().map(Dynamic::to_string).collect::<Vec<_>>())
+                    .default(0)
+                    .interact()
+                    .map_err(|err| prompt_error(&ctx, err))?;
+
+                Ok(items[picked].clone())
+            },
+        );
+}
+
+/// Replaces colors with their hex strings so they can be used as template data.
+fn colors_to_strings(data: Dynamic) -> Dynamic {
+    if data.is::<Color>() {
+        return data.cast::<Color>().to_hex().into();
+    }
+
+    if data.is::<Array>() {
+        return Dynamic::from_array(
+            data.cast::<Array>()
+                .into_iter()
+                .map(colors_to_strings)
+                .collect(),
+        );
+    }
+
+    if data.is::<Map>() {
+        return Dynamic::from_map(
+            data.cast::<Map>()
+                .into_iter()
+                .map(|(key, value)| (key, colors_to_strings(value)))
+                .collect(),
+        );
+    }
+
+    data
+}
+
 pub fn build(
+    profile: &Path,
     modules: &[PathBuf],
     settings: BTreeMap<String, Value>,
+    theme: Option<(&Theme, BTreeMap<String, Color>)>,
+    sandbox: &Sandbox,
 ) -> Result<(Engine, Arc<DashMap<PathBuf, (String, Dynamic)>>), Error> {
     let mut engine = Engine::new();
     let mut settings_mod = Module::new();
 
     let template_map = Arc::new(DashMap::new());
 
-    engine.register_fn("command", |program: String| {
-        run_command(program, None);
+    sandbox.limit(&mut engine);
+    register_color(&mut engine);
+    register_prompts(&mut engine, sandbox.isolated);
+
+    // stdout is reserved for rendered output and `--format json`
+    engine.on_print(|s| eprintln!("{}", s));
+
+    engine.register_fn("command", {
+        let sandbox = sandbox.clone();
+
+        move |ctx: NativeCallContext, program: String| run_command(ctx, &sandbox, program, None)
     });
 
-    engine.register_fn("command", |program: String, args: Array| {
-        run_command(program, Some(args));
+    engine.register_fn("command", {
+        let sandbox = sandbox.clone();
+
+        move |ctx: NativeCallContext, program: String, args: Array| {
+            run_command(ctx, &sandbox, program, Some(args))
+        }
     });
 
     engine.register_fn("template", {
         let template_map = Arc::clone(&template_map);
 
-        move |path: String, name: String, data: Dynamic| {
+        move |ctx: NativeCallContext,
+              path: String,
+              name: String,
+              data: Dynamic|
+              -> Result<(), Box<EvalAltResult>> {
             let path = PathBuf::from(path);
+            let err = |err: Error| {
+                Box::new(EvalAltResult::ErrorRuntime(
+                    err.to_string().into(),
+                    ctx.call_position(),
+                ))
+            };
 
-            assert!(
-                path.is_absolute(),
-                "templating to relative paths is unsupported {:?}",
-                &path
-            );
+            if path.is_relative() {
+                return Err(err(Error::RelativeTarget(path)));
+            }
 
+            // a second template for the same target would race with the first
             if template_map.contains_key(&path) {
-                eprintln!("{}", Error::TemplateTwice(path));
-                panic!("templating twice creates race condition");
+                return Err(err(Error::TemplateTwice(path)));
             }
 
-            template_map.insert(path, (name, data));
+            template_map.insert(path, (name, colors_to_strings(data)));
+            Ok(())
         }
     });
 
     engine.register_fn("setting", {
         let settings = settings.clone();
+        let profile = profile.to_path_buf();
 
-        move |name: String| match settings
-            .get(&name)
-            .map(|value| parse_value(value.to_owned()))
-        {
-            Some(Some(setting)) => setting,
-            _ => Dynamic::UNIT,
+        move |ctx: NativeCallContext, name: String| -> Result<Dynamic, Box<EvalAltResult>> {
+            match settings
+                .get(&name)
+                .and_then(|value| parse_value(value.to_owned()))
+            {
+                Some(setting) => Ok(setting),
+                None => Err(Box::new(EvalAltResult::ErrorRuntime(
+                    Error::SettingNotFound(name, profile.to_path_buf())
+                        .to_string()
+                        .into(),
+                    ctx.call_position(),
+                ))),
+            }
         }
     });
 
@@ -106,6 +373,26 @@ pub fn build(
 
     engine.register_static_module("settings", Arc::new(settings_mod));
 
+    if let Some((theme, palette)) = theme {
+        let mut theme_mod = Module::new();
+
+        theme_mod.set_var("name", theme.name.to_owned());
+        theme_mod.set_var("author", theme.author.to_owned().unwrap_or_default());
+        theme_mod.set_var(
+            "colors",
+            palette
+                .iter()
+                .map(|(name, color)| (name.into(), Dynamic::from(*color)))
+                .collect::<Map>(),
+        );
+
+        for (name, color) in palette {
+            theme_mod.set_var(name, color);
+        }
+
+        engine.register_static_module("theme", Arc::new(theme_mod));
+    }
+
     // load module resolver
     engine.set_module_resolver(DynamicModuleResolver::new(&modules)?);
 
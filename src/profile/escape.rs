@@ -0,0 +1,34 @@
+use serde::{Deserialize, Serialize};
+
+/// How values inserted with `{{value}}` are escaped. `{{{value}}}` is never escaped.
+#[derive(Serialize, Deserialize, Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Escape {
+    #[default]
+    None,
+    Html,
+    Shell,
+    Json,
+}
+
+fn shell_escape(data: &str) -> String {
+    format!("'{}'", data.replace('\'', r"'\''"))
+}
+
+fn json_escape(data: &str) -> String {
+    let quoted = serde_json::to_string(data).expect("strings always serialize");
+
+    // the surrounding quotes are left to the template
+    quoted[1..quoted.len() - 1].to_owned()
+}
+
+impl Escape {
+    pub fn func(&self) -> fn(&str) -> String {
+        match self {
+            Escape::None => handlebars::no_escape,
+            Escape::Html => handlebars::html_escape,
+            Escape::Shell => shell_escape,
+            Escape::Json => json_escape,
+        }
+    }
+}
@@ -0,0 +1,162 @@
+use super::{ApplyOptions, Profile};
+use crate::sink::{Memory, Prefix};
+use crate::{error::Error, relative_path::*};
+use serde::Deserialize;
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::{Path, PathBuf};
+use std::{fs, io};
+use toml::Value;
+
+/// Fixed inputs of a profile test, read from `tests/<profile>/test.toml`.
+#[derive(Deserialize, Default, Debug)]
+pub struct TestConfig {
+    /// Settings used instead of overrides from the command line or environment.
+    #[serde(default)]
+    pub settings: BTreeMap<String, Value>,
+    /// The environment seen by the `env` helper, variables that aren't listed are unset.
+    #[serde(default)]
+    pub env: BTreeMap<String, String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Outcome {
+    /// The output matches the expected file.
+    Passed,
+    /// The output differs from the expected file, starting at the given line.
+    Changed(usize),
+    /// There is no expected file for the target.
+    Missing,
+    /// An expected file exists for a target the profile no longer templates.
+    Stale,
+    /// The expected file was written or removed by `--update`.
+    Updated,
+}
+
+#[derive(Debug)]
+pub struct TestResult {
+    /// The target path, or the expected file for stale results.
+    pub target: PathBuf,
+    pub expected: PathBuf,
+    pub outcome: Outcome,
+}
+
+impl TestResult {
+    pub fn failed(&self) -> bool {
+        !matches!(self.outcome, Outcome::Passed | Outcome::Updated)
+    }
+}
+
+fn walk(dir: &Path, found: &mut BTreeSet<PathBuf>) -> io::Result<()> {
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+
+        if path.is_dir() {
+            walk(&path, found)?;
+        } else {
+            found.insert(path);
+        }
+    }
+
+    Ok(())
+}
+
+/// Line number, starting at 1, of the first line that differs.
+fn first_difference(expected: &str, actual: &str) -> usize {
+    expected
+        .lines()
+        .zip(actual.lines())
+        .position(|(expected, actual)| expected != actual)
+        .unwrap_or_else(|| expected.lines().count().min(actual.lines().count()))
+        + 1
+}
+
+/// Applies a profile in memory and compares every target against `tests/<profile>/files/`.
+///
+/// Expected files mirror the absolute target path, `/home/me/.bashrc` is compared against
+/// `tests/<profile>/files/home/me/.bashrc`. With `update` the expected files are rewritten to
+/// match instead of failing. Modules run isolated, `command` does nothing, prompts take their
+/// default and `env` only sees the variables of `test.toml`.
+pub fn test(profile: RelativePath, update: bool) -> Result<Vec<TestResult>, Error> {
+    test_in(&root()?, profile, update)
+}
+
+/// Like `test` with the profiles and tests of `root` rather than the global root.
+pub fn test_in(root: &Path, profile: RelativePath, update: bool) -> Result<Vec<TestResult>, Error> {
+    // named like the profile, so `/elsewhere/desktop.toml` is tested in `tests/desktop/`
+    let path = profile.resolve_in(root, Dir::Profiles, "toml")?;
+    let dir = Dir::Tests
+        .base_in(root)
+        .join(Dir::Profiles.component_name_in(root, &path)?);
+    let files = dir.join("files");
+    let config = match fs::read_to_string(dir.join("test.toml")) {
+        Ok(buf) => toml::from_str(&buf)?,
+        Err(err) if err.kind() == io::ErrorKind::NotFound => TestConfig::default(),
+        Err(err) => return Err(err.into()),
+    };
+
+    let mut sink = Memory::default();
+    let options = ApplyOptions {
+        backup: false,
+        after_template: false,
+    };
+
+    Profile::open_isolated_in(root, profile, config.settings, config.env)?
+        .apply(&options, &mut sink)?;
+
+    let expected_files = Prefix::new(files.to_path_buf());
+    let mut stale = BTreeSet::new();
+    let mut results = Vec::new();
+
+    if files.is_dir() {
+        walk(&files, &mut stale)?;
+    }
+
+    for (target, output) in sink.files {
+        let expected = expected_files.resolve(&target);
+        let output = String::from_utf8_lossy(&output).into_owned();
+        let outcome = match fs::read_to_string(&expected) {
+            Ok(buf) if buf == output => Outcome::Passed,
+            Ok(buf) => Outcome::Changed(first_difference(&buf, &output)),
+            Err(err) if err.kind() == io::ErrorKind::NotFound => Outcome::Missing,
+            Err(err) => return Err(err.into()),
+        };
+
+        stale.remove(&expected);
+
+        let outcome = match (update, outcome) {
+            (true, Outcome::Changed(_) | Outcome::Missing) => {
+                if let Some(parent) = expected.parent() {
+                    fs::create_dir_all(parent)?;
+                }
+
+                fs::write(&expected, output)?;
+                Outcome::Updated
+            }
+            (_, outcome) => outcome,
+        };
+
+        results.push(TestResult {
+            target,
+            expected,
+            outcome,
+        });
+    }
+
+    for expected in stale {
+        let outcome = match update {
+            true => {
+                fs::remove_file(&expected)?;
+                Outcome::Updated
+            }
+            false => Outcome::Stale,
+        };
+
+        results.push(TestResult {
+            target: expected.to_path_buf(),
+            expected,
+            outcome,
+        });
+    }
+
+    Ok(results)
+}
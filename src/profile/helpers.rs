@@ -0,0 +1,367 @@
+use crate::color::Color;
+use handlebars::{
+    Context, Handlebars, Helper, HelperDef, JsonRender, JsonValue, RenderContext, RenderError,
+    ScopedJson,
+};
+use heck::{ToKebabCase, ToLowerCamelCase, ToSnakeCase, ToTitleCase, ToUpperCamelCase};
+use std::collections::BTreeMap;
+
+type HelperFn = fn(&Helper) -> Result<JsonValue, RenderError>;
+
+/// Built-in helpers registered on every profile unless `builtin_helpers = false`.
+pub const HELPERS: [(&str, HelperFn); 38] = [
+    ("upper", |h| Ok(string(h, 0)?.to_uppercase().into())),
+    ("lower", |h| Ok(string(h, 0)?.to_lowercase().into())),
+    ("snake_case", |h| Ok(string(h, 0)?.to_snake_case().into())),
+    ("kebab_case", |h| Ok(string(h, 0)?.to_kebab_case().into())),
+    ("camel_case", |h| {
+        Ok(string(h, 0)?.to_lower_camel_case().into())
+    }),
+    ("pascal_case", |h| {
+        Ok(string(h, 0)?.to_upper_camel_case().into())
+    }),
+    ("title_case", |h| Ok(string(h, 0)?.to_title_case().into())),
+    ("trim", |h| Ok(string(h, 0)?.trim().into())),
+    ("replace", |h| {
+        Ok(string(h, 0)?.replace(&string(h, 1)?, &string(h, 2)?).into())
+    }),
+    ("join", join),
+    ("default", default),
+    ("indent", indent),
+    ("json", json),
+    ("toml", toml),
+    ("yaml", yaml),
+    ("add", |h| arithmetic(h, i64::checked_add, |a, b| a + b)),
+    ("sub", |h| arithmetic(h, i64::checked_sub, |a, b| a - b)),
+    ("mul", |h| arithmetic(h, i64::checked_mul, |a, b| a * b)),
+    ("div", |h| {
+        nonzero_divisor(h)?;
+        arithmetic(h, i64::checked_div, |a, b| a / b)
+    }),
+    ("mod", |h| {
+        nonzero_divisor(h)?;
+        arithmetic(h, i64::checked_rem, |a, b| a % b)
+    }),
+    ("min", |h| arithmetic(h, |a, b| Some(a.min(b)), f64::min)),
+    ("max", |h| arithmetic(h, |a, b| Some(a.max(b)), f64::max)),
+    ("cmp", cmp),
+    ("range", range),
+    ("env", |h| env(h, |name| std::env::var(name).ok())),
+    ("round", |h| Ok(number(h, 0)?.round().into())),
+    ("hex", |h| Ok(color(h, 0)?.to_hex().into())),
+    ("rgb", |h| Ok(color(h, 0)?.to_rgb().into())),
+    ("rgba", |h| Ok(color(h, 0)?.to_rgba().into())),
+    ("hsl", |h| Ok(color(h, 0)?.to_hsl().into())),
+    ("rgb_floats", |h| {
+        Ok(color(h, 0)?.to_floats().to_vec().into())
+    }),
+    ("ansi256", |h| Ok(color(h, 0)?.to_ansi256().into())),
+    ("lighten", |h| {
+        Ok(color(h, 0)?.lighten(number(h, 1)?).to_hex().into())
+    }),
+    ("darken", |h| {
+        Ok(color(h, 0)?.darken(number(h, 1)?).to_hex().into())
+    }),
+    ("mix", |h| {
+        let weight = match optional(h, 2) {
+            Some(_) => number(h, 2)?,
+            None => 0.5,
+        };
+
+        Ok(color(h, 0)?.mix(&color(h, 1)?, weight).to_hex().into())
+    }),
+    ("alpha", |h| {
+        Ok(color(h, 0)?.with_alpha(number(h, 1)?).to_hex().into())
+    }),
+    ("contrast", |h| {
+        Ok(color(h, 0)?.contrast(&color(h, 1)?).into())
+    }),
+    ("readable", |h| {
+        let candidates = (1..h.params().len())
+            .map(|idx| color(h, idx))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok(color(h, 0)?.readable(&candidates).to_hex().into())
+    }),
+];
+
+struct BuiltinHelper<F>(F);
+
+impl<F> HelperDef for BuiltinHelper<F>
+where
+    F: Fn(&Helper) -> Result<JsonValue, RenderError> + Send + Sync,
+{
+    fn call_inner<'reg: 'rc, 'rc>(
+        &self,
+        h: &Helper<'reg, 'rc>,
+        registry: &'reg Handlebars<'reg>,
+        ctx: &'rc Context,
+        rc: &mut RenderContext<'reg, 'rc>,
+    ) -> Result<ScopedJson<'reg, 'rc>, RenderError> {
+        // Handlebars calls a helper for a bare `{{max}}` even when the data has a `max`, look the
+        // variable up instead so the built-ins don't shadow existing template data
+        if h.params().is_empty() && h.hash().is_empty() && !h.is_block() {
+            let value = rc.evaluate(ctx, h.name())?;
+
+            if value.is_missing() && registry.strict_mode() {
+                return Err(RenderError::strict_error(Some(&h.name().to_owned())));
+            }
+
+            return Ok(value);
+        }
+
+        (self.0)(h).map(ScopedJson::Derived)
+    }
+}
+
+/// Registers the built-in helpers, with `env` only seeing the variables in `env` when given one
+/// rather than those of the host.
+pub fn register(registry: &mut Handlebars, env: Option<BTreeMap<String, String>>) {
+    for (name, helper) in HELPERS {
+        registry.register_helper(name, Box::new(BuiltinHelper(helper)));
+    }
+
+    if let Some(vars) = env {
+        let helper = move |h: &Helper| self::env(h, |name| vars.get(name).cloned());
+
+        registry.register_helper("env", Box::new(BuiltinHelper(helper)));
+    }
+}
+
+fn error(h: &Helper, message: impl AsRef<str>) -> RenderError {
+    RenderError::new(format!("`{}` helper: {}", h.name(), message.as_ref()))
+}
+
+fn optional<'a>(h: &'a Helper, idx: usize) -> Option<&'a JsonValue> {
+    h.param(idx)
+        .filter(|param| !param.is_value_missing())
+        .map(|param| param.value())
+}
+
+fn param<'a>(h: &'a Helper, idx: usize) -> Result<&'a JsonValue, RenderError> {
+    optional(h, idx).ok_or_else(|| error(h, format!("missing parameter {}", idx + 1)))
+}
+
+fn string(h: &Helper, idx: usize) -> Result<String, RenderError> {
+    Ok(param(h, idx)?.render())
+}
+
+fn color(h: &Helper, idx: usize) -> Result<Color, RenderError> {
+    Color::parse(&string(h, idx)?).map_err(|err| error(h, err.to_string()))
+}
+
+fn as_number(value: &JsonValue) -> Option<f64> {
+    match value {
+        JsonValue::String(s) => s.trim().parse().ok(),
+        value => value.as_f64(),
+    }
+}
+
+fn as_integer(value: &JsonValue) -> Option<i64> {
+    match value {
+        JsonValue::String(s) => s.trim().parse().ok(),
+        value => value.as_i64(),
+    }
+}
+
+fn number(h: &Helper, idx: usize) -> Result<f64, RenderError> {
+    as_number(param(h, idx)?)
+        .ok_or_else(|| error(h, format!("parameter {} must be a number", idx + 1)))
+}
+
+fn integer(h: &Helper, idx: usize) -> Result<i64, RenderError> {
+    as_integer(param(h, idx)?)
+        .ok_or_else(|| error(h, format!("parameter {} must be an integer", idx + 1)))
+}
+
+fn arithmetic(
+    h: &Helper,
+    int: fn(i64, i64) -> Option<i64>,
+    float: fn(f64, f64) -> f64,
+) -> Result<JsonValue, RenderError> {
+    // stay in integers when possible so `{{add 1 1}}` renders as `2` rather than `2.0`
+    if let (Some(a), Some(b)) = (as_integer(param(h, 0)?), as_integer(param(h, 1)?)) {
+        if let Some(result) = int(a, b) {
+            return Ok(result.into());
+        }
+    }
+
+    Ok(float(number(h, 0)?, number(h, 1)?).into())
+}
+
+/// `inf` and `NaN` serialize as `null` and would silently render as nothing.
+fn nonzero_divisor(h: &Helper) -> Result<(), RenderError> {
+    match number(h, 1)? == 0.0 {
+        true => Err(error(h, "division by zero")),
+        false => Ok(()),
+    }
+}
+
+fn cmp(h: &Helper) -> Result<JsonValue, RenderError> {
+    let (a, b) = (param(h, 0)?, param(h, 1)?);
+    let ordering = match (as_number(a), as_number(b)) {
+        (Some(a), Some(b)) => a.partial_cmp(&b),
+        _ => Some(a.render().cmp(&b.render())),
+    };
+
+    Ok(ordering.map(|ordering| ordering as i64).into())
+}
+
+fn join(h: &Helper) -> Result<JsonValue, RenderError> {
+    let separator = optional(h, 1).map(JsonRender::render).unwrap_or_default();
+    let items = param(h, 0)?
+        .as_array()
+        .ok_or_else(|| error(h, "parameter 1 must be an array"))?;
+
+    Ok(items
+        .iter()
+        .map(JsonRender::render)
+        .collect::<Vec<_>>()
+        .join(&separator)
+        .into())
+}
+
+fn default(h: &Helper) -> Result<JsonValue, RenderError> {
+    match optional(h, 0) {
+        None | Some(JsonValue::Null) => Ok(param(h, 1)?.clone()),
+        Some(JsonValue::String(s)) if s.is_empty() => Ok(param(h, 1)?.clone()),
+        Some(value) => Ok(value.clone()),
+    }
+}
+
+fn indent(h: &Helper) -> Result<JsonValue, RenderError> {
+    let width = usize::try_from(integer(h, 1)?).map_err(|_| error(h, "width must be positive"))?;
+    let prefix = " ".repeat(width);
+
+    Ok(string(h, 0)?
+        .split('\n')
+        .map(|line| match line.is_empty() {
+            true => line.to_owned(),
+            false => format!("{}{}", prefix, line),
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+        .into())
+}
+
+fn json(h: &Helper) -> Result<JsonValue, RenderError> {
+    let value = param(h, 0)?;
+    let pretty = h
+        .hash_get("pretty")
+        .and_then(|pretty| pretty.value().as_bool())
+        .unwrap_or(false);
+    let json = match pretty {
+        true => serde_json::to_string_pretty(value),
+        false => serde_json::to_string(value),
+    };
+
+    json.map(JsonValue::from)
+        .map_err(|err| RenderError::from_error("`json` helper: could not serialize", err))
+}
+
+fn toml(h: &Helper) -> Result<JsonValue, RenderError> {
+    let value = toml::Value::try_from(param(h, 0)?)
+        .map_err(|err| RenderError::from_error("`toml` helper: could not serialize", err))?;
+
+    // only tables serialize as documents, everything else is written as an inline value
+    match value {
+        toml::Value::Table(_) => toml::to_string(&value)
+            .map(JsonValue::from)
+            .map_err(|err| RenderError::from_error("`toml` helper: could not serialize", err)),
+        value => Ok(value.to_string().into()),
+    }
+}
+
+fn yaml(h: &Helper) -> Result<JsonValue, RenderError> {
+    Ok(yaml_lines(param(h, 0)?).join("\n").into())
+}
+
+/// Quotes strings that YAML would otherwise read as something else, like `yes`, `1.0` or `a: b`.
+fn yaml_string(string: &str) -> String {
+    let plain = string.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_' || c == '/')
+        && string
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '_' | '-' | '.' | '/'))
+        && !matches!(
+            string.to_lowercase().as_str(),
+            "y" | "n" | "yes" | "no" | "on" | "off" | "true" | "false" | "null"
+        );
+
+    match plain {
+        true => string.to_owned(),
+        // JSON strings are valid double quoted YAML scalars
+        false => JsonValue::from(string).to_string(),
+    }
+}
+
+/// Writes a value as block style YAML, nested values are indented by two spaces and strings
+/// never span lines.
+fn yaml_lines(value: &JsonValue) -> Vec<String> {
+    // the first line follows `lead`, the ones after it are indented to line up with it
+    let indented = |lead: &str, value: &JsonValue| {
+        yaml_lines(value)
+            .into_iter()
+            .enumerate()
+            .map(|(idx, line)| match idx {
+                0 => format!("{}{}", lead, line),
+                _ => format!("{:width$}{}", "", line, width = lead.len()),
+            })
+            .collect::<Vec<_>>()
+    };
+
+    match value {
+        JsonValue::Array(items) if !items.is_empty() => {
+            items.iter().flat_map(|item| indented("- ", item)).collect()
+        }
+        JsonValue::Object(map) if !map.is_empty() => map
+            .iter()
+            .flat_map(|(key, value)| {
+                let block = match value {
+                    JsonValue::Array(items) => !items.is_empty(),
+                    JsonValue::Object(map) => !map.is_empty(),
+                    _ => false,
+                };
+
+                // lists and maps start on the line after their key
+                match block {
+                    true => [
+                        vec![format!("{}:", yaml_string(key))],
+                        indented("  ", value),
+                    ]
+                    .concat(),
+                    false => indented(&format!("{}: ", yaml_string(key)), value),
+                }
+            })
+            .collect(),
+        JsonValue::Array(_) => vec!["[]".to_owned()],
+        JsonValue::Object(_) => vec!["{}".to_owned()],
+        JsonValue::String(string) => vec![yaml_string(string)],
+        value => vec![value.to_string()],
+    }
+}
+
+/// Looks up the variable named by the first parameter, falling back to the second parameter or
+/// null when it is unset.
+fn env(h: &Helper, lookup: impl Fn(&str) -> Option<String>) -> Result<JsonValue, RenderError> {
+    Ok(lookup(&string(h, 0)?)
+        .map(JsonValue::from)
+        .unwrap_or_else(|| optional(h, 1).cloned().unwrap_or(JsonValue::Null)))
+}
+
+fn range(h: &Helper) -> Result<JsonValue, RenderError> {
+    let (start, end) = (integer(h, 0)?, integer(h, 1)?);
+    let step = match optional(h, 2) {
+        Some(_) => integer(h, 2)?,
+        None => 1,
+    };
+
+    if step <= 0 {
+        return Err(error(h, "step must be positive"));
+    }
+
+    Ok((start..end)
+        .step_by(step as usize)
+        .map(JsonValue::from)
+        .collect::<Vec<_>>()
+        .into())
+}
@@ -1,21 +1,54 @@
 use crate::error::Error;
-use crate::relative_path::{Dir, RelativePath};
+use crate::relative_path::{root, Dir, RelativePath};
 use dashmap::DashMap;
 use handlebars::Handlebars;
 use rhai::{Dynamic, Engine};
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
 use std::ops::{Deref, DerefMut};
-use std::{collections::BTreeMap, fs, path::PathBuf, sync::Arc};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
 use toml::Value;
 
 mod apply;
+mod check;
+mod document;
 mod engine;
+mod escape;
+mod golden;
+mod helpers;
 mod open;
+mod render;
+mod sandbox;
+mod schema;
+mod script_helper;
+mod theme;
+
+pub use apply::{ApplyOptions, ApplyReport, Prepared};
+pub use check::{check, check_in};
+pub use document::ProfileDocument;
+pub use engine::attended;
+pub use escape::Escape;
+pub use golden::{test, test_in, Outcome, TestConfig, TestResult};
+pub use render::Rendered;
+pub use sandbox::Sandbox;
+pub use schema::{SettingSchema, SettingType};
+pub use theme::Theme;
 
 pub struct Profile {
+    /// The reconf root the profile and its components were resolved in.
+    root: PathBuf,
+    path: PathBuf,
     engine: Engine,
     registry: Handlebars<'static>,
+    escape: Escape,
+    escapes: BTreeMap<String, Escape>,
+    helpers: Vec<PathBuf>,
+    /// Templates and partials by their name in the registry.
+    templates: Vec<(String, PathBuf)>,
+    partials: Vec<(String, PathBuf)>,
     modules: Vec<PathBuf>,
+    sources: Vec<PathBuf>,
     template_map: Arc<DashMap<PathBuf, (String, Dynamic)>>,
 }
 
@@ -23,7 +56,10 @@ pub struct Profile {
 pub struct ProfileData {
     #[serde(rename = "profile")]
     pub inner: ProfileDataInner,
+    #[serde(default)]
     pub settings: BTreeMap<String, Value>,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub schema: BTreeMap<String, SettingSchema>,
 }
 
 #[derive(Serialize, Deserialize, Debug)]
@@ -35,24 +71,93 @@ pub struct ProfileDataInner {
     pub helpers: Vec<PathBuf>,
     #[serde(default)]
     pub templates: Vec<PathBuf>,
+    #[serde(default)]
+    pub partials: Vec<PathBuf>,
+    /// Theme from `themes/` exposed to modules as the `theme` module.
+    pub theme: Option<PathBuf>,
+    #[serde(default)]
+    pub escape: Escape,
+    /// Per-template escape overrides keyed by template name.
+    #[serde(default)]
+    pub escapes: BTreeMap<String, Escape>,
+    /// Fail rendering when a template uses a missing variable.
+    #[serde(default)]
+    pub strict: bool,
+    /// Register the built-in helpers, disable to avoid clashing with the profile's own helpers.
+    #[serde(default = "default_true")]
+    pub builtin_helpers: bool,
+    /// Limits for modules and helpers, for profiles using scripts from elsewhere.
+    #[serde(default, skip_serializing_if = "Sandbox::is_unrestricted")]
+    pub sandbox: Sandbox,
+}
+
+fn default_true() -> bool {
+    true
 }
 
 pub fn set_setting(profile: RelativePath, name: String, value: Value) -> Result<(), Error> {
-    let path = profile.resolve(Dir::Profiles, "toml")?;
-    let mut data: ProfileData = toml::from_str(&fs::read_to_string(&path)?)?;
-    data.settings.insert(name, value);
-    fs::write(path, toml::to_string(&data)?)?;
-    Ok(())
+    set_setting_in(&root()?, profile, name, value)
+}
+
+/// Like `set_setting` with the profiles of `root` rather than the global root.
+pub fn set_setting_in(
+    root: &Path,
+    profile: RelativePath,
+    name: String,
+    value: Value,
+) -> Result<(), Error> {
+    let mut doc = ProfileDocument::open_in(root, &profile)?;
+    doc.data.settings.insert(name.clone(), value.clone());
+    schema::validate(&doc.data.schema, &mut doc.data.settings.clone(), &doc.path)?;
+    doc.set_setting(&name, &value);
+    doc.save()
 }
 
 pub fn rm_setting(profile: RelativePath, name: String) -> Result<(), Error> {
-    let path = profile.resolve(Dir::Profiles, "toml")?;
-    let mut data: ProfileData = toml::from_str(&fs::read_to_string(&path)?)?;
-    data.settings
+    rm_setting_in(&root()?, profile, name)
+}
+
+/// Like `rm_setting` with the profiles of `root` rather than the global root.
+pub fn rm_setting_in(root: &Path, profile: RelativePath, name: String) -> Result<(), Error> {
+    let mut doc = ProfileDocument::open_in(root, &profile)?;
+    doc.data
+        .settings
         .remove(&name)
-        .ok_or(Error::SettingNotFound(name, path.to_owned()))?;
-    fs::write(&path, toml::to_string(&data)?)?;
-    Ok(())
+        .ok_or(Error::SettingNotFound(name.clone(), doc.path.to_owned()))?;
+    schema::validate(&doc.data.schema, &mut doc.data.settings.clone(), &doc.path)?;
+    doc.rm_setting(&name);
+    doc.save()
+}
+
+pub fn set_theme(profile: RelativePath, theme: RelativePath) -> Result<(), Error> {
+    set_theme_in(&root()?, profile, theme)
+}
+
+/// Like `set_theme` with the profiles and themes of `root` rather than the global root.
+pub fn set_theme_in(root: &Path, profile: RelativePath, theme: RelativePath) -> Result<(), Error> {
+    let mut doc = ProfileDocument::open_in(root, &profile)?;
+    Theme::open_in(root, &theme)?;
+    doc.set_theme(&theme)?;
+    doc.save()
+}
+
+impl Profile {
+    /// Every file the profile was built from, including the profile itself.
+    pub fn sources(&self) -> &[PathBuf] {
+        &self.sources
+    }
+}
+
+impl ProfileData {
+    pub fn components(&self, dir: Dir) -> &[PathBuf] {
+        match dir {
+            Dir::Helpers => &self.helpers,
+            Dir::Modules => &self.modules,
+            Dir::Partials => &self.partials,
+            Dir::Templates => &self.templates,
+            _ => unreachable!(),
+        }
+    }
 }
 
 impl Deref for ProfileData {
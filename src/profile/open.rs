@@ -1,61 +1,267 @@
-use super::{engine, Profile, ProfileData};
-use crate::{error::Error, relative_path::*};
-use handlebars::Handlebars;
-use std::{fs::File, io::Read, path::PathBuf};
+use super::apply::collected_across;
+use super::{engine, helpers, schema, script_helper::ScriptHelper, Profile, ProfileData, Theme};
+use crate::{diagnostic::Diagnostic, error::Error, relative_path::*};
+use handlebars::{Handlebars, TemplateError};
+use rhai::Engine;
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::{fs, fs::File, io::Read};
+use toml::Value;
 
-fn resolve_path_vec(vec: &[PathBuf], dir: Dir, ext: &str) -> Result<Vec<PathBuf>, Error> {
+fn resolve_path_vec(
+    root: &Path,
+    vec: &[PathBuf],
+    dir: Dir,
+    ext: &str,
+) -> Result<Vec<PathBuf>, Error> {
     Ok(vec
         .iter()
         .map(|path| -> Result<_, _> {
-            Ok(RelativePath::from(path.to_path_buf()).resolve(dir, ext)?)
+            Ok(RelativePath::from(path.to_path_buf()).resolve_in(root, dir, ext)?)
         })
         .collect::<Result<Vec<_>, Error>>()?)
 }
 
+fn syntax_error(path: &Path, err: TemplateError) -> Error {
+    Error::TemplateSyntaxError(
+        path.to_path_buf(),
+        Box::new(Diagnostic::template(path, &err)),
+    )
+}
+
+/// The setting of `data` that an override named `name` applies to: the exact name if the profile
+/// has it and otherwise one that only differs in case, so `RECONF_SETTING_FONTSIZE` reaches
+/// `fontSize`. Unknown names are kept as they are.
+fn setting_name(data: &ProfileData, name: &str) -> String {
+    let mut known = data.schema.keys().chain(data.settings.keys());
+
+    if known.clone().any(|known| known == name) {
+        return name.to_owned();
+    }
+
+    known
+        .find(|known| known.eq_ignore_ascii_case(name))
+        .map_or_else(|| name.to_owned(), String::to_owned)
+}
+
+fn read(root: &Path, path: RelativePath) -> Result<(PathBuf, ProfileData), Error> {
+    let mut buf = String::new();
+
+    let path = path.resolve_in(root, Dir::Profiles, "toml")?;
+
+    File::open(&path)?.read_to_string(&mut buf)?;
+
+    let data = toml::from_str(&buf)?;
+
+    Ok((path, data))
+}
+
 impl Profile {
-    pub fn open(path: RelativePath) -> Result<Profile, Error> {
-        let mut buf = String::new();
+    pub fn open(path: RelativePath, overrides: BTreeMap<String, Value>) -> Result<Profile, Error> {
+        Self::open_in(&root()?, path, overrides)
+    }
 
-        File::open(path.resolve(Dir::Profiles, "toml")?)?.read_to_string(&mut buf)?;
+    /// Opens a profile from the reconf root `root` rather than the global one, the backup of an
+    /// apply is written to this root as well.
+    pub fn open_in(
+        root: &Path,
+        path: RelativePath,
+        overrides: BTreeMap<String, Value>,
+    ) -> Result<Profile, Error> {
+        let (path, data) = read(root, path)?;
 
-        let data: ProfileData = toml::from_str(&buf)?;
-        let mut registry = Handlebars::new();
-        let helpers = resolve_path_vec(&data.helpers, Dir::Helpers, "rhai")?;
-        let modules = resolve_path_vec(&data.modules, Dir::Modules, "rhai")?;
-        let templates = resolve_path_vec(&data.templates, Dir::Templates, "hbs")?;
-        let (engine, template_map) = engine::build(&modules, data.settings)?;
+        Self::build(root, path, data, overrides)
+    }
 
-        // load helpers into Registry
-        for helper in &helpers {
-            registry
-                .register_script_helper_file(
-                    &helper
-                        .file_stem()
-                        .ok_or(Error::NoEmptyFileName)?
-                        .to_string_lossy()
-                        .into_owned(),
-                    helper,
-                )
-                .map_err(|err| Error::ScriptHelperError(err.into()))?;
+    /// Opens a profile from `root` whose modules and templates can't reach the host, for `test`,
+    /// see `Sandbox::isolated`. `env` holds the only environment variables templates see.
+    pub(super) fn open_isolated_in(
+        root: &Path,
+        path: RelativePath,
+        overrides: BTreeMap<String, Value>,
+        env: BTreeMap<String, String>,
+    ) -> Result<Profile, Error> {
+        let (path, mut data) = read(root, path)?;
+
+        data.inner.sandbox.isolated = true;
+        data.inner.sandbox.env = env;
+        Self::build(root, path, data, overrides)
+    }
+
+    /// Opens the profiles of a single apply, see `apply_all`.
+    ///
+    /// A profile with a `[schema]` only gets the overrides it declares, so a setting of one
+    /// profile can be overridden without the others rejecting it. An override no profile takes
+    /// is an error. Every profile is opened before failing with all their errors.
+    pub fn open_all(
+        profiles: &[RelativePath],
+        overrides: &BTreeMap<String, Value>,
+    ) -> Result<Vec<Profile>, Error> {
+        Self::open_all_in(&root()?, profiles, overrides)
+    }
+
+    /// Like `open_all` with the profiles of `root` rather than the global root.
+    pub fn open_all_in(
+        root: &Path,
+        profiles: &[RelativePath],
+        overrides: &BTreeMap<String, Value>,
+    ) -> Result<Vec<Profile>, Error> {
+        let read = profiles
+            .iter()
+            .map(|profile| read(root, profile.clone()))
+            .collect::<Result<Vec<_>, _>>()?;
+        let takes = |data: &ProfileData, name: &String| {
+            data.schema.is_empty() || data.schema.contains_key(&setting_name(data, name))
+        };
+
+        for name in overrides.keys() {
+            if !read.iter().any(|(_, data)| takes(data, name)) {
+                return Err(Error::UnknownOverride(name.to_owned()));
+            }
+        }
+
+        let mut opened = Vec::new();
+        let mut errors = Vec::new();
+
+        for (path, data) in read {
+            let overrides = overrides
+                .iter()
+                .filter(|(name, _)| takes(&data, name))
+                .map(|(name, value)| (name.to_owned(), value.to_owned()))
+                .collect();
+
+            match Self::build(root, path, data, overrides) {
+                Ok(profile) => opened.push(profile),
+                Err(err) => errors.push(err),
+            }
         }
 
-        // load templates into Registry
-        for template in &templates {
-            registry.register_template_file(
-                &template
-                    .file_stem()
-                    .ok_or(Error::NoEmptyFileName)?
-                    .to_string_lossy()
-                    .into_owned(),
-                template,
-            )?;
+        collected_across(errors)?;
+        Ok(opened)
+    }
+
+    fn build(
+        root: &Path,
+        path: PathBuf,
+        mut data: ProfileData,
+        overrides: BTreeMap<String, Value>,
+    ) -> Result<Profile, Error> {
+        // overrides only live for this run and are never written back to the profile
+        let overrides = overrides
+            .into_iter()
+            .map(|(name, value)| (setting_name(&data, &name), value))
+            .collect::<Vec<_>>();
+
+        data.settings.extend(overrides);
+        schema::validate(&data.schema, &mut data.settings, &path)?;
+
+        let mut registry = Handlebars::new();
+
+        let mut helper_engine = Engine::new();
+
+        // helpers compile with the registry's engine, so it has to be limited up front
+        data.sandbox.limit(&mut helper_engine);
+        registry.set_engine(helper_engine);
+        registry.register_escape_fn(data.escape.func());
+        registry.set_strict_mode(data.strict);
+
+        if data.builtin_helpers {
+            let sandbox = &data.inner.sandbox;
+
+            helpers::register(&mut registry, sandbox.isolated.then(|| sandbox.env.clone()));
         }
 
+        let helpers = resolve_path_vec(root, &data.helpers, Dir::Helpers, "rhai")?;
+        let modules = resolve_path_vec(root, &data.modules, Dir::Modules, "rhai")?;
+        let templates = resolve_path_vec(root, &data.templates, Dir::Templates, "hbs")?;
+        let partials = resolve_path_vec(root, &data.partials, Dir::Partials, "hbs")?;
+        let theme = match &data.theme {
+            Some(theme) => Some(Theme::open_in(
+                root,
+                &RelativePath::from(theme.to_path_buf()),
+            )?),
+            None => None,
+        };
+        let palette = match &theme {
+            Some((path, theme)) => Some((theme, theme.palette(path)?)),
+            None => None,
+        };
+        let (engine, template_map) =
+            engine::build(&path, &modules, data.settings, palette, &data.inner.sandbox)?;
+        let sources = [&path]
+            .into_iter()
+            .chain(&helpers)
+            .chain(&modules)
+            .chain(&templates)
+            .chain(&partials)
+            .chain(theme.as_ref().map(|(path, _)| path))
+            .cloned()
+            .collect();
+
+        let named = |dir: Dir, paths: Vec<PathBuf>| {
+            paths
+                .into_iter()
+                .map(|path| Ok((dir.component_name_in(root, &path)?, path)))
+                .collect::<Result<Vec<_>, Error>>()
+        };
+        let templates = named(Dir::Templates, templates)?;
+        let partials = named(Dir::Partials, partials)?;
+
         Ok(Profile {
+            root: root.to_path_buf(),
+            path,
             engine,
             registry,
+            escape: data.inner.escape,
+            escapes: data.inner.escapes,
+            helpers,
+            templates,
+            partials,
             modules,
+            sources,
             template_map,
         })
     }
+
+    /// Loads the helpers, templates and partials into the registry, returning the ones that
+    /// failed to load.
+    ///
+    /// Run before every render, so each render reports broken components and sees their
+    /// latest contents.
+    pub(super) fn register(&mut self) -> Vec<Error> {
+        let mut errors = Vec::new();
+
+        // load helpers into Registry
+        for helper in &self.helpers {
+            let name = helper.file_stem().unwrap_or_default().to_string_lossy();
+
+            match ScriptHelper::open(&self.registry, helper) {
+                Ok(script) => self.registry.register_helper(&name, Box::new(script)),
+                Err(err) => errors.push(err),
+            }
+        }
+
+        // load templates and partials into Registry
+        for (name, template) in &self.templates {
+            if let Err(err) = self.registry.register_template_file(name, template) {
+                errors.push(syntax_error(template, err));
+            }
+        }
+
+        for (name, partial) in &self.partials {
+            let registered = match fs::read_to_string(partial) {
+                Ok(source) => self.registry.register_partial(name, source),
+                Err(err) => {
+                    errors.push(err.into());
+                    continue;
+                }
+            };
+
+            if let Err(err) = registered {
+                errors.push(syntax_error(partial, err));
+            }
+        }
+
+        errors
+    }
 }
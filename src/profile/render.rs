@@ -0,0 +1,73 @@
+use super::Profile;
+use crate::error::Error;
+use serde_json::Value as JsonValue;
+use std::path::{Path, PathBuf};
+
+/// A template rendered for a single target path.
+#[derive(Debug, Clone)]
+pub struct Rendered {
+    pub target: PathBuf,
+    pub template: String,
+    /// The data the module passed to `template`, as seen by Handlebars.
+    pub data: JsonValue,
+    pub output: String,
+}
+
+impl Profile {
+    /// Renders the targets the modules templated whose path and template name pass `filter`.
+    ///
+    /// Targets that fail to render are left out and their error is added to `errors`, nothing is
+    /// rendered if helpers or templates failed to register.
+    pub(super) fn render_targets(
+        &mut self,
+        filter: impl Fn(&Path, &str) -> bool,
+        errors: &mut Vec<Error>,
+    ) -> Vec<Rendered> {
+        let registration_errors = self.register();
+
+        // rendering would only repeat the errors of helpers and templates that didn't register
+        if !registration_errors.is_empty() {
+            errors.splice(0..0, registration_errors);
+            return Vec::new();
+        }
+
+        let mut selected = self
+            .template_map
+            .iter()
+            .filter(|entry| filter(entry.key(), &entry.value().0))
+            .map(|entry| (entry.key().to_path_buf(), entry.value().clone()))
+            .collect::<Vec<_>>();
+
+        selected.sort_by(|(a, _), (b, _)| a.cmp(b));
+
+        selected
+            .into_iter()
+            .filter_map(|(target, (template, data))| {
+                let rendered = self.render_template(&template, &data).and_then(|output| {
+                    Ok(Rendered {
+                        output,
+                        data: serde_json::to_value(&data)?,
+                        target,
+                        template,
+                    })
+                });
+
+                rendered.map_err(|err| errors.push(err)).ok()
+            })
+            .collect()
+    }
+
+    /// Evaluates the modules and renders the selected targets without writing anything.
+    ///
+    /// `filter` is given the target path and template name of everything the modules templated.
+    pub fn render(&mut self, filter: impl Fn(&Path, &str) -> bool) -> Result<Vec<Rendered>, Error> {
+        let mut errors = Vec::new();
+
+        self.evaluate(&mut errors);
+
+        let rendered = self.render_targets(filter, &mut errors);
+
+        self.collected(errors)?;
+        Ok(rendered)
+    }
+}
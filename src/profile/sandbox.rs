@@ -0,0 +1,69 @@
+use rhai::Engine;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/// Limits for the Rhai modules and helpers of a profile, unset limits keep Rhai's defaults.
+///
+/// Rhai has no filesystem functions of its own and `import` only resolves the profile's modules,
+/// so `command` is the only way a script reaches outside the engine. Unknown keys are rejected so
+/// a misspelled limit isn't silently ignored.
+#[derive(Serialize, Deserialize, Debug, Default, Clone, PartialEq, Eq)]
+#[serde(deny_unknown_fields)]
+pub struct Sandbox {
+    /// Operations a single script may run before it is stopped, guards against endless loops.
+    pub max_operations: Option<u64>,
+    /// Nested function calls, Rhai defaults to 8 in debug and 64 in release builds.
+    pub max_call_levels: Option<usize>,
+    /// Length of a string in bytes.
+    pub max_string_size: Option<usize>,
+    /// Number of elements in an array.
+    pub max_array_size: Option<usize>,
+    /// Number of properties in an object map.
+    pub max_map_size: Option<usize>,
+    /// Programs `command` may run, compared to the program exactly as the script passes it.
+    /// Every program is allowed when unset, none when empty.
+    pub commands: Option<Vec<String>>,
+    /// Set by `reconf test` so results don't depend on the host: `command` does nothing and
+    /// prompts take their default, failing without one.
+    #[serde(skip)]
+    pub isolated: bool,
+    /// The only environment variables the `env` helper sees when isolated, any other variable
+    /// is unset.
+    #[serde(skip)]
+    pub env: BTreeMap<String, String>,
+}
+
+impl Sandbox {
+    /// Applies the limits to an engine, must be done before scripts are compiled.
+    pub fn limit(&self, engine: &mut Engine) {
+        if let Some(operations) = self.max_operations {
+            engine.set_max_operations(operations);
+        }
+
+        if let Some(levels) = self.max_call_levels {
+            engine.set_max_call_levels(levels);
+        }
+
+        if let Some(size) = self.max_string_size {
+            engine.set_max_string_size(size);
+        }
+
+        if let Some(size) = self.max_array_size {
+            engine.set_max_array_size(size);
+        }
+
+        if let Some(size) = self.max_map_size {
+            engine.set_max_map_size(size);
+        }
+    }
+
+    pub fn is_unrestricted(&self) -> bool {
+        self == &Self::default()
+    }
+
+    pub fn allows_command(&self, program: &str) -> bool {
+        self.commands
+            .as_ref()
+            .is_none_or(|commands| commands.iter().any(|command| command == program))
+    }
+}
@@ -0,0 +1,148 @@
+use crate::error::Error;
+use serde::{Deserialize, Serialize};
+use std::{collections::BTreeMap, fmt, path::Path};
+use toml::Value;
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum SettingType {
+    String,
+    Integer,
+    Float,
+    Boolean,
+    Array,
+    Table,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct SettingSchema {
+    #[serde(rename = "type")]
+    pub kind: Option<SettingType>,
+    pub default: Option<Value>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub allowed: Vec<Value>,
+    pub description: Option<String>,
+}
+
+impl SettingType {
+    /// The type of a value, `None` for datetimes which settings can't hold.
+    pub fn of(value: &Value) -> Option<Self> {
+        match value {
+            Value::String(_) => Some(SettingType::String),
+            Value::Integer(_) => Some(SettingType::Integer),
+            Value::Float(_) => Some(SettingType::Float),
+            Value::Boolean(_) => Some(SettingType::Boolean),
+            Value::Datetime(_) => None,
+            Value::Array(_) => Some(SettingType::Array),
+            Value::Table(_) => Some(SettingType::Table),
+        }
+    }
+}
+
+impl fmt::Display for SettingType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            SettingType::String => "string",
+            SettingType::Integer => "integer",
+            SettingType::Float => "float",
+            SettingType::Boolean => "boolean",
+            SettingType::Array => "array",
+            SettingType::Table => "table",
+        })
+    }
+}
+
+impl SettingSchema {
+    /// Checks a single value against the declared type and allowed values.
+    pub fn check(&self, name: &str, value: &Value, profile: &Path) -> Result<(), Error> {
+        if let Some(kind) = self.kind {
+            if SettingType::of(value) != Some(kind) {
+                return Err(Error::InvalidSettingType(
+                    name.to_string(),
+                    kind.to_string(),
+                    value.type_str().to_string(),
+                    profile.to_path_buf(),
+                ));
+            }
+        }
+
+        if !self.allowed.is_empty() && !self.allowed.contains(value) {
+            return Err(Error::SettingNotAllowed(
+                name.to_string(),
+                value.to_string(),
+                self.allowed
+                    .iter()
+                    .map(Value::to_string)
+                    .collect::<Vec<_>>()
+                    .join(", "),
+                profile.to_path_buf(),
+            ));
+        }
+
+        Ok(())
+    }
+}
+
+fn has_datetime(value: &Value) -> bool {
+    match value {
+        Value::Datetime(_) => true,
+        Value::Array(values) => values.iter().any(has_datetime),
+        Value::Table(table) => table.values().any(has_datetime),
+        _ => false,
+    }
+}
+
+/// Validates `settings` against `schema` and fills in defaults for missing settings.
+///
+/// An empty schema accepts any settings so profiles without a `[schema]` table keep working.
+pub fn validate(
+    schema: &BTreeMap<String, SettingSchema>,
+    settings: &mut BTreeMap<String, Value>,
+    profile: &Path,
+) -> Result<(), Error> {
+    // modules can't be given datetimes, reject them here rather than when the engine is built
+    let datetime =
+        settings
+            .iter()
+            .chain(schema.iter().filter_map(|(name, setting)| {
+                setting.default.as_ref().map(|default| (name, default))
+            }))
+            .find(|(_, value)| has_datetime(value));
+
+    if let Some((name, _)) = datetime {
+        return Err(Error::DatetimeSetting(
+            name.to_owned(),
+            profile.to_path_buf(),
+        ));
+    }
+
+    if schema.is_empty() {
+        return Ok(());
+    }
+
+    for name in settings.keys() {
+        if !schema.contains_key(name) {
+            return Err(Error::UnknownSetting(
+                name.to_owned(),
+                profile.to_path_buf(),
+            ));
+        }
+    }
+
+    for (name, setting) in schema {
+        let value = match (settings.get(name), &setting.default) {
+            (Some(value), _) => value,
+            (None, Some(default)) => settings.entry(name.to_owned()).or_insert(default.clone()),
+            (None, None) => {
+                return Err(Error::MissingSetting(
+                    name.to_owned(),
+                    profile.to_path_buf(),
+                ))
+            }
+        };
+
+        setting.check(name, value, profile)?;
+    }
+
+    Ok(())
+}
@@ -0,0 +1,70 @@
+use crate::{diagnostic::Diagnostic, error::Error};
+use handlebars::{
+    Context, Handlebars, Helper, HelperDef, JsonValue, RenderContext, RenderError, ScopedJson,
+};
+use rhai::serde::{from_dynamic, to_dynamic};
+use rhai::{Dynamic, Scope, AST};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+/// A Rhai helper that remembers its file so errors can point into it.
+///
+/// Behaves like `Handlebars::register_script_helper_file`, the script gets the `params` array
+/// and `hash` map and its result is used as the helper's value.
+pub struct ScriptHelper {
+    path: PathBuf,
+    script: AST,
+}
+
+impl ScriptHelper {
+    pub fn open(registry: &Handlebars, path: &Path) -> Result<Self, Error> {
+        let script = registry
+            .engine()
+            .compile_file(path.to_path_buf())
+            .map_err(|err| {
+                Error::ScriptHelperError(
+                    path.to_path_buf(),
+                    Box::new(Diagnostic::rhai(path, *err, &[])),
+                )
+            })?;
+
+        Ok(Self {
+            path: path.to_path_buf(),
+            script,
+        })
+    }
+}
+
+impl HelperDef for ScriptHelper {
+    fn call_inner<'reg: 'rc, 'rc>(
+        &self,
+        h: &Helper<'reg, 'rc>,
+        registry: &'reg Handlebars<'reg>,
+        _: &'rc Context,
+        _: &mut RenderContext<'reg, 'rc>,
+    ) -> Result<ScopedJson<'reg, 'rc>, RenderError> {
+        let params: Dynamic = to_dynamic(h.params().iter().map(|p| p.value()).collect::<Vec<_>>())?;
+        let hash: Dynamic = to_dynamic(
+            h.hash()
+                .iter()
+                .map(|(key, value)| (key.to_string(), value.value()))
+                .collect::<HashMap<_, _>>(),
+        )?;
+        let mut scope = Scope::new();
+
+        scope.push_dynamic("params", params);
+        scope.push_dynamic("hash", hash);
+
+        let result = registry
+            .engine()
+            .eval_ast_with_scope::<Dynamic>(&mut scope, &self.script)
+            .map_err(|err| {
+                RenderError::from_error(
+                    &format!("script helper `{}` failed", h.name()),
+                    Diagnostic::rhai(&self.path, *err, &[]),
+                )
+            })?;
+
+        Ok(ScopedJson::Derived(from_dynamic::<JsonValue>(&result)?))
+    }
+}
@@ -0,0 +1,48 @@
+use crate::{color::Color, error::Error, relative_path::*};
+use serde::Deserialize;
+use std::{
+    collections::BTreeMap,
+    fs,
+    path::{Path, PathBuf},
+};
+
+/// A named color palette loaded from `themes/`.
+///
+/// Colors use base16 names (`base00` to `base0F`, `base10` to `base17` for base24) as a baseline
+/// but any name is accepted.
+#[derive(Deserialize, Debug)]
+pub struct Theme {
+    pub name: String,
+    pub author: Option<String>,
+    #[serde(default)]
+    pub colors: BTreeMap<String, String>,
+}
+
+impl Theme {
+    pub fn open(theme: &RelativePath) -> Result<(PathBuf, Theme), Error> {
+        Self::open_in(&root()?, theme)
+    }
+
+    /// Like `open` with the themes below `root` rather than the global root.
+    pub fn open_in(root: &Path, theme: &RelativePath) -> Result<(PathBuf, Theme), Error> {
+        let path = theme.resolve_in(root, Dir::Themes, Dir::Themes.ext_str())?;
+        let theme = toml::from_str(&fs::read_to_string(&path)?)?;
+
+        Ok((path, theme))
+    }
+
+    /// Parses every color in the palette.
+    pub fn palette(&self, path: &Path) -> Result<BTreeMap<String, Color>, Error> {
+        self.colors
+            .iter()
+            .map(|(name, color)| match Color::parse(color) {
+                Ok(color) => Ok((name.to_owned(), color)),
+                Err(err) => Err(Error::InvalidThemeColor(
+                    name.to_owned(),
+                    path.to_path_buf(),
+                    err.into(),
+                )),
+            })
+            .collect()
+    }
+}
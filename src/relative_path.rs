@@ -1,13 +1,56 @@
 use crate::error::Error;
 use std::ffi::OsString;
 use std::path::{Path, PathBuf};
+use std::sync::{PoisonError, RwLock};
+use std::{env, fs, io};
 
-#[derive(Copy, Clone)]
+/// Environment variable that overrides the reconf root directory.
+pub const ROOT_ENV: &str = "RECONF_HOME";
+
+/// File name marking a directory (or one of its ancestors) as a reconf root.
+pub const ROOT_MARKER: &str = "reconf.toml";
+
+static ROOT: RwLock<Option<PathBuf>> = RwLock::new(None);
+
+/// Sets the root directory used by every `Dir` lookup that isn't given one, taking precedence over
+/// the environment and marker discovery. Later calls replace the root for lookups made afterwards,
+/// to use several roots at once pass them explicitly, e.g. to `Profile::open_in`.
+pub fn set_root(root: PathBuf) {
+    *ROOT.write().unwrap_or_else(PoisonError::into_inner) = Some(root);
+}
+
+/// Finds the reconf root, in order of precedence: `set_root`, `RECONF_HOME`, the closest
+/// `reconf.toml` marker from the current directory upward and finally `$XDG_CONFIG_HOME/reconf`.
+pub fn root() -> Result<PathBuf, Error> {
+    if let Some(root) = ROOT.read().unwrap_or_else(PoisonError::into_inner).as_ref() {
+        return Ok(root.to_path_buf());
+    }
+
+    if let Some(root) = env::var_os(ROOT_ENV).filter(|root| !root.is_empty()) {
+        return Ok(PathBuf::from(root));
+    }
+
+    if let Ok(cwd) = env::current_dir() {
+        if let Some(root) = cwd.ancestors().find(|dir| dir.join(ROOT_MARKER).is_file()) {
+            return Ok(root.to_path_buf());
+        }
+    }
+
+    let mut config = dirs::config_dir().ok_or(Error::NoConfigDir)?;
+    config.push("reconf");
+
+    Ok(config)
+}
+
+#[derive(Copy, Clone, Debug)]
 pub enum Dir {
     Helpers,
     Modules,
+    Partials,
     Profiles,
     Templates,
+    Themes,
+    Tests,
     Config,
 }
 
@@ -16,8 +59,11 @@ impl Dir {
         match self {
             Dir::Helpers => "helpers",
             Dir::Modules => "modules",
+            Dir::Partials => "partials",
             Dir::Profiles => "profiles",
             Dir::Templates => "templates",
+            Dir::Themes => "themes",
+            Dir::Tests => "tests",
             Dir::Config => ".",
         }
     }
@@ -26,8 +72,10 @@ impl Dir {
         match self {
             Dir::Helpers => "rhai",
             Dir::Modules => "rhai",
+            Dir::Partials => "hbs",
             Dir::Profiles => "toml",
             Dir::Templates => "hbs",
+            Dir::Themes => "toml",
             _ => "",
         }
     }
@@ -36,23 +84,91 @@ impl Dir {
         match self {
             Dir::Helpers => "helper",
             Dir::Modules => "module",
+            Dir::Partials => "partial",
             Dir::Profiles => "profile",
             Dir::Templates => "template",
+            Dir::Themes => "theme",
+            Dir::Tests => "test",
             Dir::Config => "internal",
         }
     }
 
     pub fn as_base(&self) -> Result<PathBuf, Error> {
-        let mut config = dirs::config_dir().ok_or(Error::NoConfigDir)?;
+        Ok(self.base_in(&root()?))
+    }
+
+    /// This directory below `root` rather than the global root.
+    pub fn base_in(&self, root: &Path) -> PathBuf {
+        match self {
+            Dir::Config => root.to_path_buf(),
+            dir => root.join(dir.path_str()),
+        }
+    }
+
+    /// Names a resolved component by its path relative to this directory without the extension,
+    /// e.g. `templates/a/b.hbs` is named `a/b`. Components outside the directory use their stem.
+    pub fn component_name(&self, path: &Path) -> Result<String, Error> {
+        self.component_name_in(&root()?, path)
+    }
+
+    /// Like `component_name` with this directory below `root`.
+    pub fn component_name_in(&self, root: &Path, path: &Path) -> Result<String, Error> {
+        let base = self.base_in(root);
+        let name = match path.strip_prefix(&base) {
+            Ok(relative) => relative.with_extension(""),
+            Err(_) => PathBuf::from(path.file_stem().ok_or(Error::NoEmptyFileName)?),
+        };
 
-        config.push("reconf");
-        config.push(self.path_str());
+        if name.as_os_str().is_empty() {
+            return Err(Error::NoEmptyFileName);
+        }
 
-        Ok(config)
+        Ok(name
+            .components()
+            .map(|part| part.as_os_str().to_string_lossy())
+            .collect::<Vec<_>>()
+            .join("/"))
+    }
+
+    /// Lists every file with this directory's extension, relative to its base and sorted.
+    pub fn list(&self) -> Result<Vec<PathBuf>, Error> {
+        fn walk(dir: &Path, ext: &str, found: &mut Vec<PathBuf>) -> io::Result<()> {
+            for entry in fs::read_dir(dir)? {
+                let path = entry?.path();
+
+                if path.is_dir() {
+                    walk(&path, ext, found)?;
+                } else if path.extension() == Some(&OsString::from(ext)) {
+                    found.push(path);
+                }
+            }
+
+            Ok(())
+        }
+
+        let base = self.as_base()?;
+        let mut found = Vec::new();
+
+        if base.is_dir() {
+            walk(&base, self.ext_str(), &mut found)?;
+        }
+
+        let mut found = found
+            .into_iter()
+            .map(|path| {
+                path.strip_prefix(&base)
+                    .map(Path::to_path_buf)
+                    .unwrap_or(path)
+            })
+            .collect::<Vec<_>>();
+
+        found.sort();
+
+        Ok(found)
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct RelativePath(PathBuf);
 
 impl From<PathBuf> for RelativePath {
@@ -63,7 +179,17 @@ impl From<PathBuf> for RelativePath {
 
 impl RelativePath {
     pub fn resolve(&self, dir: Dir, ext: impl AsRef<str>) -> Result<PathBuf, Error> {
-        let path = self.resolve_unchecked(dir, ext)?;
+        self.resolve_in(&root()?, dir, ext)
+    }
+
+    /// Like `resolve` with `dir` below `root` rather than the global root.
+    pub fn resolve_in(
+        &self,
+        root: &Path,
+        dir: Dir,
+        ext: impl AsRef<str>,
+    ) -> Result<PathBuf, Error> {
+        let path = self.resolve_unchecked_in(root, dir, ext);
 
         if path.exists() {
             return Ok(path);
@@ -73,23 +199,26 @@ impl RelativePath {
     }
 
     pub fn resolve_unchecked(&self, dir: Dir, ext: impl AsRef<str>) -> Result<PathBuf, Error> {
+        Ok(self.resolve_unchecked_in(&root()?, dir, ext))
+    }
+
+    /// Like `resolve_unchecked` with `dir` below `root` rather than the global root.
+    pub fn resolve_unchecked_in(&self, root: &Path, dir: Dir, ext: impl AsRef<str>) -> PathBuf {
         // make path absolute
         let mut path = if self.0.is_absolute() {
             self.0.to_path_buf()
         } else {
-            let mut dir = dir.as_base()?;
-            dir.push(&self.0);
-            dir
+            dir.base_in(root).join(&self.0)
         };
 
         // if the path exists we're done
         if path.exists() {
-            return Ok(path);
+            return path;
         }
 
         // if the path has the right extension it's where it should be
         if path.extension() == Some(&OsString::from(ext.as_ref())) {
-            return Ok(path);
+            return path;
         }
 
         // append the provided file extension and recheck
@@ -103,7 +232,7 @@ impl RelativePath {
         new_ext.push(ext.as_ref());
         path.set_extension(new_ext);
 
-        Ok(path)
+        path
     }
 
     pub fn path(&self) -> &Path {
@@ -0,0 +1,186 @@
+use crate::cli::Format;
+use reconf::{component, error::Error, profile, relative_path::*};
+use serde_json::json;
+use std::{fs, path::Path};
+
+/// The example profile, written by `init` so a fresh root has something to apply.
+const EXAMPLE: [(Dir, &str, &str); 7] = [
+    (
+        Dir::Profiles,
+        "readme.toml",
+        include_str!("../example/profiles/readme.toml"),
+    ),
+    (
+        Dir::Modules,
+        "readme/readme.rhai",
+        include_str!("../example/modules/readme/readme.rhai"),
+    ),
+    (
+        Dir::Modules,
+        "readme/urls.rhai",
+        include_str!("../example/modules/readme/urls.rhai"),
+    ),
+    (
+        Dir::Modules,
+        "readme/factorial.rhai",
+        include_str!("../example/modules/readme/factorial.rhai"),
+    ),
+    (
+        Dir::Helpers,
+        "hex_to_rgb.rhai",
+        include_str!("../example/helpers/hex_to_rgb.rhai"),
+    ),
+    (
+        Dir::Templates,
+        "readme.hbs",
+        include_str!("../example/templates/readme.hbs"),
+    ),
+    (
+        Dir::Themes,
+        "gruvbox-dark.toml",
+        include_str!("../example/themes/gruvbox-dark.toml"),
+    ),
+];
+
+fn starter(dir: Dir, name: &str) -> String {
+    match dir {
+        Dir::Profiles => format!(
+            "[profile]\n\
+            name = {:?}\n\
+            \n\
+            # All of these are optional. File extensions are optional. Paths relative to \
+            {{root}}/{{folder}}, the root defaults to .config/reconf\n\
+            modules = []\n\
+            helpers = []\n\
+            templates = []\n\
+            partials = []\n\
+            \n\
+            [settings]\n",
+            name
+        ),
+        Dir::Modules => format!(
+            "// Module {}\n\
+            \n\
+            // USAGE: template(\"/absolute/path\", \"template\", #{{ ... }});\n",
+            name
+        ),
+        Dir::Helpers => format!(
+            "// Helper {}, arguments are available in `params` and `hash`.\n\
+            params[0]\n",
+            name
+        ),
+        Dir::Partials => format!("{{{{!-- Partial {} --}}}}\n", name),
+        Dir::Templates => format!("{{{{!-- Template {} --}}}}\n", name),
+        Dir::Themes => format!(
+            "name = {:?}\n\
+            \n\
+            # Colors follow the base16 naming scheme, any other names may be added.\n\
+            [colors]\n\
+            {}",
+            name,
+            [
+                "#000000", "#222222", "#444444", "#666666", "#999999", "#bbbbbb", "#dddddd",
+                "#ffffff", "#ff0000", "#ff8800", "#ffff00", "#00ff00", "#00ffff", "#0000ff",
+                "#ff00ff", "#884400",
+            ]
+            .iter()
+            .enumerate()
+            .map(|(idx, color)| format!("base{:02X} = {:?}\n", idx, color))
+            .collect::<String>()
+        ),
+        Dir::Tests | Dir::Config => unreachable!(),
+    }
+}
+
+fn write_new(path: &Path, contents: &str) -> Result<(), Error> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+
+    fs::write(path, contents)?;
+
+    Ok(())
+}
+
+/// Creates the directory layout under the reconf root along with the example profile.
+pub fn init(format: Format) -> Result<(), Error> {
+    for dir in [
+        Dir::Profiles,
+        Dir::Modules,
+        Dir::Helpers,
+        Dir::Templates,
+        Dir::Partials,
+        Dir::Themes,
+    ] {
+        fs::create_dir_all(dir.as_base()?)?;
+    }
+
+    let mut created = Vec::new();
+
+    for (dir, name, contents) in EXAMPLE {
+        let path = dir.as_base()?.join(name);
+
+        if path.exists() {
+            eprintln!("WARNING! Not overwriting existing file {:?}", path);
+            continue;
+        }
+
+        write_new(&path, contents)?;
+        created.push(path);
+    }
+
+    match format {
+        Format::Json => println!("{}", json!({ "root": root()?, "created": created })),
+        Format::Text => println!("initialized reconf in {:?}", root()?),
+    }
+
+    Ok(())
+}
+
+/// Writes a starter file for a new component and registers it in `profile` if given.
+pub fn new(
+    dir: Dir,
+    name: RelativePath,
+    profile: Option<RelativePath>,
+    format: Format,
+) -> Result<(), Error> {
+    let path = name.resolve_unchecked(dir, dir.ext_str())?;
+
+    if path.exists() {
+        return Err(Error::AlreadyExists(dir.component_str().to_string(), path));
+    }
+
+    let stem = name
+        .path()
+        .file_stem()
+        .ok_or(Error::NoEmptyFileName)?
+        .to_string_lossy();
+
+    write_new(&path, &starter(dir, &stem))?;
+
+    let added = match (dir, profile) {
+        (Dir::Profiles, Some(_)) => {
+            eprintln!("WARNING! Profiles cannot be added to a profile");
+            Ok(())
+        }
+        (Dir::Themes, Some(profile)) => profile::set_theme(profile, name),
+        (_, Some(profile)) => component::add(profile, dir, name),
+        (_, None) => Ok(()),
+    };
+
+    // a mistyped profile or a component it already has shouldn't leave the new file behind
+    if let Err(err) = added {
+        fs::remove_file(&path)?;
+        return Err(err);
+    }
+
+    match format {
+        Format::Json => println!(
+            "{}",
+            json!({ "created": { "kind": dir.component_str(), "path": path } })
+        ),
+        Format::Text => println!("created {} {:?}", dir.component_str(), path),
+    }
+
+    Ok(())
+}
@@ -0,0 +1,93 @@
+use std::collections::BTreeMap;
+use std::path::{Component, Path, PathBuf};
+use std::{fs, io};
+
+/// Where apply and restore read and write targets, paths are always absolute.
+pub trait Sink {
+    /// Reads a file, returning `None` if it doesn't exist.
+    fn read(&self, path: &Path) -> io::Result<Option<Vec<u8>>>;
+
+    fn write(&mut self, path: &Path, contents: &[u8]) -> io::Result<()>;
+
+    /// Where a file written through this sink ends up, for reporting.
+    fn locate(&self, path: &Path) -> PathBuf {
+        path.to_path_buf()
+    }
+}
+
+/// The real filesystem.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct Filesystem;
+
+impl Sink for Filesystem {
+    fn read(&self, path: &Path) -> io::Result<Option<Vec<u8>>> {
+        match fs::read(path) {
+            Ok(contents) => Ok(Some(contents)),
+            Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(None),
+            Err(err) => Err(err),
+        }
+    }
+
+    fn write(&mut self, path: &Path, contents: &[u8]) -> io::Result<()> {
+        fs::write(path, contents)
+    }
+}
+
+/// The real filesystem below a directory, `/etc/foo` is written to `<prefix>/etc/foo`.
+///
+/// Missing parent directories are created so a whole machine config can be staged in an empty
+/// directory.
+#[derive(Debug, Clone)]
+pub struct Prefix(PathBuf);
+
+impl Prefix {
+    pub fn new(prefix: PathBuf) -> Self {
+        Self(prefix)
+    }
+
+    /// Maps an absolute path into the prefix, `..` is dropped so nothing can escape it.
+    pub fn resolve(&self, path: &Path) -> PathBuf {
+        self.0.join(
+            path.components()
+                .filter(|component| matches!(component, Component::Normal(_)))
+                .collect::<PathBuf>(),
+        )
+    }
+}
+
+impl Sink for Prefix {
+    fn read(&self, path: &Path) -> io::Result<Option<Vec<u8>>> {
+        Filesystem.read(&self.resolve(path))
+    }
+
+    fn locate(&self, path: &Path) -> PathBuf {
+        self.resolve(path)
+    }
+
+    fn write(&mut self, path: &Path, contents: &[u8]) -> io::Result<()> {
+        let path = self.resolve(path);
+
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+
+        fs::write(path, contents)
+    }
+}
+
+/// Keeps every file in memory, reads only see what was written to this sink.
+#[derive(Debug, Default, Clone)]
+pub struct Memory {
+    pub files: BTreeMap<PathBuf, Vec<u8>>,
+}
+
+impl Sink for Memory {
+    fn read(&self, path: &Path) -> io::Result<Option<Vec<u8>>> {
+        Ok(self.files.get(path).cloned())
+    }
+
+    fn write(&mut self, path: &Path, contents: &[u8]) -> io::Result<()> {
+        self.files.insert(path.to_path_buf(), contents.to_vec());
+        Ok(())
+    }
+}
@@ -0,0 +1,102 @@
+use crate::{cli::Format, inspect};
+use notify_debouncer_mini::{new_debouncer, notify::RecursiveMode};
+use reconf::{
+    error::Error,
+    profile::{ApplyOptions, Profile},
+    relative_path::*,
+    sink::Sink,
+};
+use std::collections::{BTreeMap, HashSet};
+use std::{
+    path::{Path, PathBuf},
+    sync::mpsc,
+    time::Duration,
+};
+use toml::Value;
+
+/// Editors tend to save in several steps, wait for them to settle before applying.
+const DEBOUNCE: Duration = Duration::from_millis(250);
+
+/// Makes paths comparable with the absolute paths reported by the watcher.
+///
+/// Only the parent is canonicalized since the file itself may have been removed or renamed.
+fn normalize(path: &Path) -> PathBuf {
+    match (path.parent().map(Path::canonicalize), path.file_name()) {
+        (Some(Ok(parent)), Some(name)) => parent.join(name),
+        _ => path.to_path_buf(),
+    }
+}
+
+/// Applies the profiles and re-applies them every time one of their files changes.
+///
+/// Errors are printed rather than returned so a typo doesn't end the session, the files of the
+/// last profiles that opened successfully keep being watched until the profiles are fixed.
+///
+/// Only the first successful apply is backed up, later ones would replace the user's own files in
+/// the archive with the output of the previous apply.
+pub fn watch(
+    profiles: Vec<RelativePath>,
+    overrides: BTreeMap<String, Value>,
+    options: &ApplyOptions,
+    sink: &mut dyn Sink,
+    format: Format,
+) -> Result<(), Error> {
+    let (tx, rx) = mpsc::channel();
+    let mut debouncer = new_debouncer(DEBOUNCE, tx)?;
+    let mut files = profiles
+        .iter()
+        .map(|profile| Ok(normalize(&profile.resolve(Dir::Profiles, "toml")?)))
+        .collect::<Result<HashSet<_>, Error>>()?;
+    let mut watched_dirs = HashSet::new();
+    let mut options = options.clone();
+
+    loop {
+        match Profile::open_all(&profiles, &overrides) {
+            Ok(mut opened) => {
+                files = opened
+                    .iter()
+                    .flat_map(|opened| opened.sources())
+                    .map(|source| normalize(source))
+                    .collect();
+
+                match Profile::apply_all(&mut opened, &options, sink) {
+                    Ok(report) => {
+                        inspect::report(&report, format);
+                        options.backup = false;
+
+                        for profile in &profiles {
+                            eprintln!("applied profile {:?}", profile.path());
+                        }
+                    }
+                    Err(err) => inspect::error(&err, format),
+                }
+            }
+            Err(err) => inspect::error(&err, format),
+        }
+
+        // watch directories rather than files so editors that save by renaming are still seen
+        for dir in files.iter().filter_map(|file| file.parent()) {
+            if watched_dirs.insert(dir.to_path_buf()) {
+                debouncer
+                    .watcher()
+                    .watch(dir, RecursiveMode::NonRecursive)?;
+            }
+        }
+
+        // block until a file of the profile changes, ignoring everything else in the directories
+        loop {
+            match rx.recv() {
+                Ok(Ok(events))
+                    if events
+                        .iter()
+                        .any(|event| files.contains(&normalize(&event.path))) =>
+                {
+                    break
+                }
+                Ok(Ok(_)) => continue,
+                Ok(Err(err)) => inspect::error(&Error::from(err), format),
+                Err(_) => return Ok(()),
+            }
+        }
+    }
+}